@@ -1,4 +1,5 @@
 use crate::commands;
+use crate::commands::output::{emit, Event};
 use anyhow::{Context, Result};
 use log::{info, warn};
 use std::{fs, path::Path};
@@ -22,6 +23,9 @@ pub fn cmd(data_dir: &Path, plugin_path: &Path) -> Result<()> {
             "Successfully cleaned: {}",
             plugin_data_dir.to_string_lossy()
         );
+        emit(&Event::DataDirCleaned {
+            data_dir: &plugin_data_dir,
+        });
     } else {
         warn!("Data directory does not exist");
     }
@@ -1,13 +1,18 @@
-use anyhow::{bail, Context, Result};
+use super::{admin_socket_path, current_config, default_configurations, find_plugin, schema};
+use crate::commands::output::{self, coded, emit, ErrorCode, Event, TypedEvent};
+use crate::commands::project::{Package, Project};
+use anyhow::{Context, Result};
+use colored::Colorize;
 use derive_builder::Builder;
-use log::info;
-use serde::Deserialize;
+use log::{info, warn};
+use serde::Serialize;
+use serde_yaml::Value;
 use std::{
-    collections::HashMap,
-    env, fs,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
     io::{BufRead, BufReader, Read, Write},
     path::{Path, PathBuf},
-    process::{self, Command, Stdio}, ptr::write,
+    process::{self, Command, Stdio},
 };
 
 /// Mapping of plugin service names to their properties specified in
@@ -75,11 +80,144 @@ fn read_config_from_path(path: &PathBuf) -> Result<ConfigMap> {
     ))
 }
 
+/// Value of a key changed by the config file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueChange {
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Difference of a service config in the file with the config on the cluster.
+#[derive(Debug, Default, Serialize)]
+pub struct ServiceConfigDiff {
+    pub added: BTreeMap<String, Value>,
+    pub changed: BTreeMap<String, ValueChange>,
+    /// Keys missing from the file, which are reset to manifest defaults with `--prune`
+    pub reset: BTreeMap<String, ValueChange>,
+    /// Keys missing from the file, which stay on the cluster with their current values
+    pub kept: BTreeMap<String, Value>,
+}
+
+impl ServiceConfigDiff {
+    fn new(current: &BTreeMap<String, Value>, config: &HashMap<String, Value>) -> Self {
+        let mut diff = Self::default();
+        for (key, value) in config {
+            match current.get(key) {
+                None => {
+                    diff.added.insert(key.clone(), value.clone());
+                }
+                Some(old) if old != value => {
+                    diff.changed.insert(
+                        key.clone(),
+                        ValueChange {
+                            old: old.clone(),
+                            new: value.clone(),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, value) in current {
+            if !config.contains_key(key) {
+                diff.kept.insert(key.clone(), value.clone());
+            }
+        }
+        diff
+    }
+
+    /// Move kept keys, which differ from the service defaults, to reset ones.
+    fn prune(&mut self, service_name: &str, defaults: Option<&Value>) {
+        let kept = std::mem::take(&mut self.kept);
+        for (key, value) in kept {
+            match defaults.and_then(|defaults| defaults.get(&key)) {
+                Some(default) if *default != value => {
+                    self.reset.insert(
+                        key,
+                        ValueChange {
+                            old: value,
+                            new: default.clone(),
+                        },
+                    );
+                }
+                Some(_) => {
+                    self.kept.insert(key, value);
+                }
+                None => {
+                    warn!(
+                        "key {key} of service {service_name} has no default value in manifest.yaml, it can't be reset - skipping"
+                    );
+                    self.kept.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.reset.is_empty()
+            && self.kept.is_empty()
+    }
+
+    /// Keys with values to set on the cluster.
+    fn into_updates(self) -> BTreeMap<String, Value> {
+        let mut updates = self.added;
+        updates.extend(
+            self.changed
+                .into_iter()
+                .chain(self.reset)
+                .map(|(key, change)| (key, change.new)),
+        );
+        updates
+    }
+
+    fn print(&self, plugin: &Package, service_name: &str) {
+        let header = format!(
+            "Service {service_name} of plugin {} {}",
+            plugin.name, plugin.version
+        );
+        if self.is_empty() {
+            println!("{header}: up to date");
+            return;
+        }
+
+        let show = |value: &Value| serde_json::to_string(value).unwrap_or_default();
+        println!("{header}:");
+        for (key, value) in &self.added {
+            println!("{}", format!("  + {key}: {}", show(value)).green());
+        }
+        for (key, change) in &self.changed {
+            println!(
+                "{}",
+                format!("  ~ {key}: {} -> {}", show(&change.old), show(&change.new)).yellow()
+            );
+        }
+        for (key, change) in &self.reset {
+            println!(
+                "{}",
+                format!(
+                    "  - {key}: {} -> {} (reset to default)",
+                    show(&change.old),
+                    show(&change.new)
+                )
+                .red()
+            );
+        }
+        for (key, value) in &self.kept {
+            println!(
+                "{}",
+                format!("  = {key}: {} (kept on cluster)", show(value)).dimmed()
+            );
+        }
+    }
+}
+
 fn apply_service_config(
     plugin_name: &str,
     plugin_version: &str,
     service_name: &str,
-    config: &HashMap<String, serde_yaml::Value>,
+    config: &BTreeMap<String, Value>,
     admin_socket: &Path,
 ) -> Result<()> {
     let mut queries: Vec<String> = Vec::new();
@@ -129,7 +267,7 @@ fn apply_service_config(
             Box::new(picodata_admin.stderr.unwrap()),
         ];
 
- use std::fmt::Write;
+        use std::fmt::Write;
         let mut a = String::new();
         for output in outputs {
             let reader = BufReader::new(output);
@@ -141,63 +279,119 @@ fn apply_service_config(
         }
 
         if exit_status == 1 {
-            bail!("failed to execute picodata query {query}: context {a}");
+            return Err(coded(
+                ErrorCode::ConfigApplyFailed,
+                format!("failed to execute picodata query {query}: context {a}"),
+            ));
         }
     }
 
     Ok(())
 }
 
-fn apply_plugin_config(params: &Params, current_plugin_path: &str) -> Result<()> {
-    let cur_plugin_dir = env::current_dir()?
-        .join(&params.plugin_path)
-        .join(current_plugin_path);
-
-    let admin_socket = params
-        .plugin_path
-        .join(&params.data_dir)
-        .join("cluster")
-        .join("i1")
-        .join("admin.sock");
-
-    let cargo_manifest: &CargoManifest = &toml::from_str(
-        &fs::read_to_string(cur_plugin_dir.join("Cargo.toml"))
-            .context("failed to read Cargo.toml")?,
-    )
-    .context("failed to parse Cargo.toml")?;
+fn plugin_config(params: &Params, package: &Package) -> Result<ConfigMap> {
+    match &params.config_source {
+        ConfigSource::Map(map) => Ok(map.clone()),
+        ConfigSource::Path(path) => read_config_from_path(&package.dir.join(path)),
+    }
+}
 
-    let config: ConfigMap = match &params.config_source {
-        ConfigSource::Map(map) => map.clone(),
-        ConfigSource::Path(path) => read_config_from_path(&cur_plugin_dir.join(path))?,
+// Check config against `default_configuration` of the services from the built manifest
+fn validate_plugin_config(project: &Project, package: &Package, config: &ConfigMap) -> Result<()> {
+    let defaults = match default_configurations(project, package) {
+        Ok(defaults) => defaults,
+        Err(e) => {
+            warn!("{e:#} - skipping validation of the config");
+            return Ok(());
+        }
     };
 
-    for (service_name, service_config) in config {
+    let lint = schema::validate(&defaults, config);
+    for problem in &lint.unknown_keys {
+        warn!("config of plugin {}: {problem}", package.name);
+    }
+    if !lint.errors.is_empty() {
+        return Err(coded(
+            ErrorCode::InvalidConfig,
+            format!(
+                "config of plugin {} does not match default configuration of its services in manifest.yaml: {}",
+                package.name,
+                lint.errors.join(", ")
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn apply_plugin_config(
+    params: &Params,
+    project: &Project,
+    package: &Package,
+    config: ConfigMap,
+) -> Result<()> {
+    let admin_socket = admin_socket_path(&params.plugin_path, &params.data_dir);
+    let config: BTreeMap<_, _> = config.into_iter().collect();
+
+    let current = current_config(package, &admin_socket).context(format!(
+        "failed to read current config of plugin {}",
+        package.name
+    ))?;
+    let mut defaults = None;
+
+    // Services removed from the file are diffed against an empty config,
+    // so all their keys are kept or reset with `--prune`
+    let service_names: BTreeSet<&String> = config.keys().chain(current.keys()).collect();
+    for service_name in service_names {
+        let mut diff = ServiceConfigDiff::new(
+            current.get(service_name).unwrap_or(&BTreeMap::new()),
+            config.get(service_name).unwrap_or(&HashMap::new()),
+        );
+        // Resets are computed before the dry run check to be shown in the preview
+        if params.prune && !diff.kept.is_empty() {
+            let defaults: &BTreeMap<String, Value> = match &defaults {
+                Some(defaults) => defaults,
+                None => defaults.insert(default_configurations(project, package)?),
+            };
+            diff.prune(service_name, defaults.get(service_name));
+        }
+
+        if output::is_json() {
+            emit(&TypedEvent::ServiceConfigDiff {
+                plugin: &package.name,
+                version: &package.version,
+                service: service_name,
+                diff: &diff,
+                dry_run: params.dry_run,
+            });
+        } else {
+            diff.print(package, service_name);
+        }
+
+        if params.dry_run {
+            continue;
+        }
+
         apply_service_config(
-            &cargo_manifest.package.name,
-            &cargo_manifest.package.version,
-            &service_name,
-            &service_config,
+            &package.name,
+            &package.version,
+            service_name,
+            &diff.into_updates(),
             &admin_socket,
         )
         .context(format!(
             "failed to apply service config for service {service_name}"
         ))?;
+        emit(&Event::ServiceConfigApplied {
+            plugin: &package.name,
+            version: &package.version,
+            service: service_name,
+        });
     }
 
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct Package {
-    name: String,
-    version: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct CargoManifest {
-    package: Package,
-}
-
 #[derive(Debug, Clone)]
 pub enum ConfigSource {
     Map(ConfigMap),
@@ -220,6 +414,15 @@ pub struct Params {
     plugin_path: PathBuf,
     #[builder(default)]
     plugin_name: Option<String>,
+    /// Only print the difference with the config on the cluster
+    #[builder(default)]
+    dry_run: bool,
+    /// Reset keys missing from the config to their default values from the manifest
+    #[builder(default)]
+    prune: bool,
+    /// Apply the config without checking it against the manifest
+    #[builder(default)]
+    skip_validation: bool,
 }
 
 impl ParamsBuilder {
@@ -236,61 +439,47 @@ impl ParamsBuilder {
 }
 
 pub fn cmd(params: &Params) -> Result<()> {
+    let project = Project::load(&params.plugin_path)?;
+
     // If plugin name flag was specified, apply config only for
     // this exact plugin
-    if let Some(plugin_name) = &params.plugin_name {
+    let packages = if let Some(plugin_name) = &params.plugin_name {
         info!("Applying plugin config for plugin {plugin_name}");
-        apply_plugin_config(params, plugin_name)?;
-        return Ok(());
-    }
-
-    let root_dir = env::current_dir()?.join(&params.plugin_path);
-
-    let cargo_toml_path = root_dir.join("Cargo.toml");
-    let cargo_toml_content = fs::read_to_string(&cargo_toml_path).context(format!(
-        "Failed to read Cargo.toml in {}",
-        &cargo_toml_path.display()
-    ))?;
-
-    let parsed_toml: toml::Value = cargo_toml_content
-        .parse()
-        .context("Failed to parse Cargo.toml")?;
-
-    if let Some(workspace) = parsed_toml.get("workspace") {
-        if let ConfigSource::Path(config_path) = &params.config_source {
-            if config_path.to_str().unwrap() != DEFAULT_PLUGIN_CONFIG_PATH {
-                println!("{WISE_PIKE}");
-                process::exit(1);
-            }
-        }
-        info!("Applying plugin config for each plugin");
-
-        if let Some(members) = workspace.get("members") {
-            if let Some(members_array) = members.as_array() {
-                for member in members_array {
-                    let member_str = member.as_str();
-                    if member_str.is_none() {
-                        continue;
-                    }
-
-                    if !root_dir
-                        .join(member_str.unwrap())
-                        .join("manifest.yaml.template")
-                        .exists()
-                    {
-                        continue;
+        vec![find_plugin(&project, plugin_name)?]
+    } else {
+        if project.is_workspace {
+            if let ConfigSource::Path(config_path) = &params.config_source {
+                if config_path.to_str().unwrap() != DEFAULT_PLUGIN_CONFIG_PATH {
+                    if output::is_json() {
+                        return Err(coded(
+                            ErrorCode::PluginNameRequired,
+                            "custom config path in workspace requires --plugin-name",
+                        ));
                     }
-                    apply_plugin_config(params, member_str.unwrap())?;
+                    println!("{WISE_PIKE}");
+                    process::exit(1);
                 }
             }
+            info!("Applying plugin config for each plugin");
+        } else {
+            info!("Applying plugin config");
         }
+        project.plugins().collect()
+    };
 
-        return Ok(());
+    // Nothing is sent to the cluster if config of any plugin is invalid
+    let mut configs = vec![];
+    for package in packages {
+        let config = plugin_config(params, package)?;
+        if !params.skip_validation {
+            validate_plugin_config(&project, package, &config)?;
+        }
+        configs.push((package, config));
     }
 
-    info!("Applying plugin config");
-
-    apply_plugin_config(params, "./")?;
+    for (package, config) in configs {
+        apply_plugin_config(params, &project, package, config)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use derive_builder::Builder;
+use std::path::PathBuf;
+
+use super::{admin_socket_path, current_config, select_plugin, ServicesConfig};
+use crate::commands::output::{self, emit, TypedEvent};
+use crate::commands::project::Project;
+
+#[derive(Debug, Builder)]
+pub struct Params {
+    #[builder(default = "PathBuf::from(\"./tmp\")")]
+    data_dir: PathBuf,
+    #[builder(default = "PathBuf::from(\"./\")")]
+    plugin_path: PathBuf,
+    /// Required in workspace with several plugins
+    #[builder(default)]
+    plugin_name: Option<String>,
+    /// Get config of the single service
+    #[builder(default)]
+    service: Option<String>,
+}
+
+/// Read config of the plugin services from the running cluster,
+/// in the same shape as `plugin_config.yaml`.
+pub fn get(params: &Params) -> Result<ServicesConfig> {
+    let project = Project::load(&params.plugin_path)?;
+    let package = select_plugin(&project, params.plugin_name.as_deref())?;
+
+    let admin_socket = admin_socket_path(&params.plugin_path, &params.data_dir);
+    let mut config = current_config(package, &admin_socket).context(format!(
+        "failed to read current config of plugin {}",
+        package.name
+    ))?;
+
+    if let Some(service) = &params.service {
+        let service_config = config.remove(service).context(format!(
+            "service {service} of plugin {} {} has no config on the cluster",
+            package.name, package.version
+        ))?;
+        config = ServicesConfig::from([(service.clone(), service_config)]);
+    }
+
+    emit(&TypedEvent::PluginConfig {
+        plugin: &package.name,
+        version: &package.version,
+        config: &config,
+    });
+
+    Ok(config)
+}
+
+pub fn cmd(params: &Params) -> Result<()> {
+    let config = get(params)?;
+    if !output::is_json() {
+        print!("{}", serde_yaml::to_string(&config)?);
+    }
+
+    Ok(())
+}
@@ -1 +1,137 @@
+use anyhow::{bail, Context, Result};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::lib::{self, BuildType, CargoOptions};
+use crate::commands::lua::{eval_on_socket, quote_lua_string};
+use crate::commands::output::{coded, ErrorCode};
+use crate::commands::project::{Package, Project};
+
 pub(crate) mod apply;
+pub(crate) mod get;
+pub(crate) mod schema;
+
+// Migration context of the plugin is stored along with the config of services
+const MIGRATION_CONTEXT_ENTITY: &str = "migration_context";
+
+/// Config of services by their names, as stored on the cluster.
+pub type ServicesConfig = BTreeMap<String, BTreeMap<String, Value>>;
+
+// Config is read and applied through the admin socket of the first instance
+fn admin_socket_path(plugin_path: &Path, data_dir: &Path) -> PathBuf {
+    plugin_path
+        .join(data_dir)
+        .join("cluster")
+        .join("i1")
+        .join("admin.sock")
+}
+
+// Name of the member directory is also accepted
+fn find_plugin<'a>(project: &'a Project, plugin_name: &str) -> Result<&'a Package> {
+    project
+        .package(plugin_name)
+        .or_else(|| {
+            project
+                .packages
+                .iter()
+                .find(|package| package.dir.ends_with(plugin_name))
+        })
+        .context(format!("plugin {plugin_name} not found in the project"))
+}
+
+// Plugin chosen by name, or the only plugin of the project
+fn select_plugin<'a>(project: &'a Project, plugin_name: Option<&str>) -> Result<&'a Package> {
+    if let Some(plugin_name) = plugin_name {
+        return find_plugin(project, plugin_name);
+    }
+
+    let plugins: Vec<_> = project.plugins().collect();
+    let [package] = plugins[..] else {
+        return Err(coded(
+            ErrorCode::PluginNameRequired,
+            "project has several plugins, choose one with --plugin-name",
+        ));
+    };
+    Ok(package)
+}
+
+// Read config of the plugin services from `_pico_plugin_config` system table,
+// its tuples are `[plugin, version, entity, key, value]`
+pub(super) fn current_config(plugin: &Package, admin_socket: &Path) -> Result<ServicesConfig> {
+    let code = format!(
+        "local plugin, version = {}, {}; \
+         local config = {{}}; \
+         for _, t in box.space._pico_plugin_config:pairs() do \
+             if t[1] == plugin and t[2] == version then \
+                 config[t[3]] = config[t[3]] or {{}}; \
+                 config[t[3]][t[4]] = t[5]; \
+             end \
+         end; \
+         return config",
+        quote_lua_string(&plugin.name),
+        quote_lua_string(&plugin.version)
+    );
+
+    let mut config: ServicesConfig =
+        match eval_on_socket(Path::new("picodata"), admin_socket, &code)? {
+            // Console prints an empty Lua table as a sequence
+            Value::Null => ServicesConfig::new(),
+            Value::Sequence(seq) if seq.is_empty() => ServicesConfig::new(),
+            config @ Value::Mapping(_) => {
+                serde_yaml::from_value(config).context("unexpected format of services config")?
+            }
+            config => bail!("unexpected format of services config: {config:?}"),
+        };
+    config.remove(MIGRATION_CONTEXT_ENTITY);
+
+    Ok(config)
+}
+
+// `manifest.yaml` of the built plugin, debug or release whichever is built later
+fn built_manifest_path(project: &Project, plugin: &Package) -> Result<PathBuf> {
+    let target_dir = project.target_dir(None);
+    [BuildType::Debug, BuildType::Release]
+        .iter()
+        .map(|build_type| {
+            lib::artifact_dir(&target_dir, build_type, &CargoOptions::default())
+                .join(&plugin.name)
+                .join(&plugin.version)
+                .join("manifest.yaml")
+        })
+        .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .max()
+        .map(|(_, path)| path)
+        .context(format!(
+            "manifest.yaml of plugin {} {} is not found in {}, build the plugin first",
+            plugin.name,
+            plugin.version,
+            target_dir.display()
+        ))
+}
+
+// `default_configuration` of every service from the built manifest, null if it is not set
+fn default_configurations(project: &Project, plugin: &Package) -> Result<BTreeMap<String, Value>> {
+    let manifest_path = built_manifest_path(project, plugin)?;
+    let manifest: Value = serde_yaml::from_str(
+        &fs::read_to_string(&manifest_path)
+            .context(format!("failed to read {}", manifest_path.display()))?,
+    )
+    .context(format!("failed to parse {}", manifest_path.display()))?;
+
+    let mut configs = BTreeMap::new();
+    let services = manifest.get("services").and_then(Value::as_sequence);
+    for service in services.into_iter().flatten() {
+        let Some(name) = service.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let config = service
+            .get("default_configuration")
+            .cloned()
+            .unwrap_or_default();
+        configs.insert(name.to_string(), config);
+    }
+
+    Ok(configs)
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use derive_builder::Builder;
+use serde_json::json;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::apply::ConfigMap;
+use super::{default_configurations, select_plugin};
+use crate::commands::output::{self, emit, Event};
+use crate::commands::project::Project;
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+#[derive(Debug, Builder)]
+pub struct Params {
+    #[builder(default = "PathBuf::from(\"./\")")]
+    plugin_path: PathBuf,
+    /// Required in workspace with several plugins
+    #[builder(default)]
+    plugin_name: Option<String>,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Sequence(_) => "array",
+        Value::Mapping(_) => "object",
+        Value::Tagged(tagged) => type_name(&tagged.value),
+    }
+}
+
+// Schema of the value inferred from its default. Null is accepted everywhere,
+// as optional fields of service configs are usually set to null.
+// Mappings accept unknown keys, services may have optional keys without defaults
+fn value_schema(default: &Value) -> serde_json::Value {
+    let mut schema = match default {
+        Value::Null => return json!({}),
+        Value::Tagged(tagged) => return value_schema(&tagged.value),
+        Value::Sequence(items) => match items.first() {
+            Some(item) => json!({ "items": value_schema(item) }),
+            None => json!({}),
+        },
+        Value::Mapping(map) if !map.is_empty() => {
+            let properties: serde_json::Map<_, _> = map
+                .iter()
+                .filter_map(|(key, value)| Some((key.as_str()?.to_string(), value_schema(value))))
+                .collect();
+            json!({ "properties": properties })
+        }
+        _ => json!({}),
+    };
+
+    schema["type"] = json!([type_name(default), "null"]);
+    if let Ok(default) = serde_json::to_value(default) {
+        schema["default"] = default;
+    }
+    schema
+}
+
+// Config of a service is a mapping even without a default
+fn service_schema(default: &Value) -> serde_json::Value {
+    let mut schema = value_schema(default);
+    schema["type"] = json!("object");
+    schema
+}
+
+/// JSON Schema of `plugin_config.yaml` inferred from `default_configuration` of the services.
+pub fn config_schema(title: &str, defaults: &BTreeMap<String, Value>) -> serde_json::Value {
+    let properties: serde_json::Map<_, _> = defaults
+        .iter()
+        .map(|(service, default)| (service.clone(), service_schema(default)))
+        .collect();
+
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// Problems of the plugin config found by [`validate`].
+#[derive(Debug, Default)]
+pub struct ConfigLint {
+    /// Unknown services and values of wrong types, the config is rejected with them
+    pub errors: Vec<String>,
+    /// Keys missing from `default_configuration`, which may be optional keys of the service
+    pub unknown_keys: Vec<String>,
+}
+
+// Check the value against the subset of JSON Schema produced by `value_schema`
+fn check(schema: &serde_json::Value, value: &Value, path: &str, lint: &mut ConfigLint) {
+    let value = match value {
+        Value::Tagged(tagged) => &tagged.value,
+        value => value,
+    };
+
+    if let Some(types) = schema.get("type") {
+        let actual = type_name(value);
+        let types: Vec<&str> = match types {
+            serde_json::Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            types => types.as_str().into_iter().collect(),
+        };
+        let matches = types
+            .iter()
+            .any(|&expected| expected == actual || (expected == "number" && actual == "integer"));
+        if !matches {
+            lint.errors
+                .push(format!("{path}: expected {}, got {actual}", types[0]));
+            return;
+        }
+    }
+
+    match value {
+        Value::Sequence(items) => {
+            if let Some(items_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(items_schema, item, &format!("{path}[{i}]"), lint);
+                }
+            }
+        }
+        Value::Mapping(map) => {
+            // Keys of mappings empty by default are not known
+            let properties = schema.get("properties");
+            for (key, item) in map {
+                let key = key
+                    .as_str()
+                    .map_or_else(|| format!("{key:?}"), str::to_string);
+                match properties.and_then(|properties| properties.get(&key)) {
+                    Some(item_schema) => {
+                        check(item_schema, item, &format!("{path}.{key}"), lint);
+                    }
+                    None if properties.is_some() => lint.unknown_keys.push(format!(
+                        "{path}.{key}: unknown key, it is missing from default_configuration"
+                    )),
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Check keys and value types of the plugin config against
+/// `default_configuration` of the services.
+pub fn validate(defaults: &BTreeMap<String, Value>, config: &ConfigMap) -> ConfigLint {
+    let mut lint = ConfigLint::default();
+    let config: BTreeMap<_, _> = config.iter().collect();
+    for (service, service_config) in config {
+        let Some(default) = defaults.get(service) else {
+            lint.errors
+                .push(format!("{service}: plugin has no such service"));
+            continue;
+        };
+
+        let service_config: BTreeMap<_, _> = service_config.iter().collect();
+        let service_config = Value::Mapping(
+            service_config
+                .into_iter()
+                .map(|(key, value)| (Value::from(key.as_str()), value.clone()))
+                .collect(),
+        );
+        check(
+            &service_schema(default),
+            &service_config,
+            service,
+            &mut lint,
+        );
+    }
+    lint
+}
+
+pub fn cmd(params: &Params) -> Result<()> {
+    let project = Project::load(&params.plugin_path)?;
+    let package = select_plugin(&project, params.plugin_name.as_deref())?;
+
+    let defaults = default_configurations(&project, package)?;
+    let schema = config_schema(
+        &format!("Config of plugin {} {}", package.name, package.version),
+        &defaults,
+    );
+
+    if output::is_json() {
+        emit(&Event::PluginConfigSchema {
+            plugin: &package.name,
+            version: &package.version,
+            schema: &schema,
+        });
+    } else {
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,324 @@
+use colored::{ColoredString, Colorize};
+use regex::Regex;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+// Plain format of picodata logs looks like
+// `2025-01-01 12:00:00.000 [4242] main/104/interactive I> message`
+static PLAIN_LEVEL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s([FSECWIVD])>\s").expect("valid regex"));
+
+/// Levels of picodata log messages, from the most severe to the least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum LogLevel {
+    Fatal,
+    Syserror,
+    Error,
+    Crit,
+    Warn,
+    Info,
+    Verbose,
+    Debug,
+}
+
+impl LogLevel {
+    fn from_plain(letter: &str) -> Option<Self> {
+        match letter {
+            "F" => Some(Self::Fatal),
+            "S" => Some(Self::Syserror),
+            "E" => Some(Self::Error),
+            "C" => Some(Self::Crit),
+            "W" => Some(Self::Warn),
+            "I" => Some(Self::Info),
+            "V" => Some(Self::Verbose),
+            "D" => Some(Self::Debug),
+            _ => None,
+        }
+    }
+
+    fn from_json(level: &str) -> Option<Self> {
+        match level.to_ascii_lowercase().as_str() {
+            "fatal" => Some(Self::Fatal),
+            "syserror" => Some(Self::Syserror),
+            "error" => Some(Self::Error),
+            "crit" | "critical" => Some(Self::Crit),
+            "warn" | "warning" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "verbose" => Some(Self::Verbose),
+            "debug" => Some(Self::Debug),
+            _ => None,
+        }
+    }
+
+    /// Parse level of a log line in plain or JSON picodata log format.
+    pub fn parse(line: &str) -> Option<Self> {
+        if line.trim_start().starts_with('{') {
+            let value: serde_json::Value = serde_json::from_str(line).ok()?;
+            return Self::from_json(value.get("level")?.as_str()?);
+        }
+
+        Self::from_plain(PLAIN_LEVEL_RE.captures(line)?.get(1)?.as_str())
+    }
+}
+
+/// Rules to choose instance log lines printed by the foreground `run`.
+///
+/// Log files of instances always receive full output.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Least severe level to print, lines without a level are always printed
+    pub level: Option<LogLevel>,
+    /// Print only lines matching the regex
+    pub grep: Option<Regex>,
+    /// Print only logs of these instances
+    pub instances: Vec<String>,
+    /// Do not print logs of these instances
+    pub quiet_instances: Vec<String>,
+}
+
+impl LogFilter {
+    /// Whether logs of the instance are printed at all, instance can be
+    /// referred either by its name or by its data dir name like `i1`.
+    pub fn is_instance_visible(&self, names: &[&str]) -> bool {
+        if names
+            .iter()
+            .any(|name| self.quiet_instances.iter().any(|q| q == name))
+        {
+            return false;
+        }
+
+        self.instances.is_empty()
+            || names
+                .iter()
+                .any(|name| self.instances.iter().any(|i| i == name))
+    }
+
+    pub fn is_line_visible(&self, line: &str, level: Option<LogLevel>) -> bool {
+        if let (Some(max_level), Some(level)) = (self.level, level) {
+            if level > max_level {
+                return false;
+            }
+        }
+
+        self.grep.as_ref().is_none_or(|re| re.is_match(line))
+    }
+}
+
+/// Colorize warnings and errors.
+pub fn highlight(line: &str, level: Option<LogLevel>) -> ColoredString {
+    match level {
+        Some(LogLevel::Fatal | LogLevel::Syserror | LogLevel::Error | LogLevel::Crit) => line.red(),
+        Some(LogLevel::Warn) => line.yellow(),
+        _ => line.normal(),
+    }
+}
+
+const DEFAULT_LOG_RETENTION: usize = 5;
+
+/// Parse size like `1024`, `512K`, `100M` or `1G` into bytes.
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (number, multiplier) = match size.char_indices().last() {
+        Some((idx, 'K' | 'k')) => (&size[..idx], 1 << 10),
+        Some((idx, 'M' | 'm')) => (&size[..idx], 1 << 20),
+        Some((idx, 'G' | 'g')) => (&size[..idx], 1 << 30),
+        _ => (size, 1),
+    };
+
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size {size}, expected value like 100M"))?;
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size {size} is too large"))
+}
+
+/// Parse interval like `30s`, `10m`, `1h` or `1d`.
+pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let interval = interval.trim();
+    let err = || format!("invalid interval {interval}, expected value like 1h");
+
+    let (idx, unit) = interval.char_indices().last().ok_or_else(err)?;
+    let secs_in_unit = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    let number: u64 = interval[..idx].trim().parse().map_err(|_| err())?;
+    let secs = number
+        .checked_mul(secs_in_unit)
+        .ok_or_else(|| format!("interval {interval} is too large"))?;
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Human(String),
+    }
+
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Human(size)) => parse_size(&size).map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn deserialize_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|interval| parse_interval(&interval).map_err(de::Error::custom))
+        .transpose()
+}
+
+fn default_retention() -> usize {
+    DEFAULT_LOG_RETENTION
+}
+
+/// Rotation policy of instance log files written by the foreground `run`.
+///
+/// Rotated files are kept next to the log as `picodata.log.1`, `picodata.log.2`
+/// and so on, where `.1` is the most recent one.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogRotation {
+    /// Rotate the log once it grows over this size in bytes
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+    /// Rotate the log once it is older than this interval
+    #[serde(default, deserialize_with = "deserialize_interval")]
+    pub rotate_interval: Option<Duration>,
+    /// Number of rotated files to keep
+    #[serde(default = "default_retention")]
+    pub retention: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            rotate_interval: None,
+            retention: DEFAULT_LOG_RETENTION,
+        }
+    }
+}
+
+/// Log file which rotates itself according to [`LogRotation`].
+pub struct RotatingLogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    opened_at: Instant,
+    rotation: LogRotation,
+}
+
+impl RotatingLogFile {
+    pub fn create(path: &Path, rotation: LogRotation) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Self::open(path)?,
+            written: 0,
+            opened_at: Instant::now(),
+            rotation,
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn needs_rotation(&self, line_len: u64) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+
+        let size_exceeded = self
+            .rotation
+            .max_size
+            .is_some_and(|max_size| self.written + line_len > max_size);
+        let interval_passed = self
+            .rotation
+            .rotate_interval
+            .is_some_and(|interval| self.opened_at.elapsed() >= interval);
+
+        size_exceeded || interval_passed
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.rotation.retention > 0 {
+            for index in (1..self.rotation.retention).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = Self::open(&self.path)?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let line_len = line.len() as u64 + 1;
+        if self.needs_rotation(line_len) {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.written += line_len;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_size;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512K"), Ok(512 << 10));
+        assert_eq!(parse_size(" 100m "), Ok(100 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+    }
+
+    #[test]
+    fn test_parse_size_overflow() {
+        assert!(parse_size("99999999999G").is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn test_parse_size_invalid_suffix() {
+        assert!(parse_size("100T").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("").is_err());
+    }
+}
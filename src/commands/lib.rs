@@ -1,42 +1,133 @@
 use anyhow::{bail, Context, Result};
 use std::fs;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::commands::output::{self, coded, ErrorCode};
 
 pub enum BuildType {
     Release,
     Debug,
 }
 
-#[allow(clippy::needless_pass_by_value)]
-pub fn cargo_build(build_type: BuildType, target_dir: &PathBuf, build_dir: &PathBuf) -> Result<()> {
-    let mut args = vec!["build"];
-    if let BuildType::Release = build_type {
-        args.push("--release");
+/// Options of `cargo build` passed through by `run`, `plugin build` and `plugin pack`.
+#[derive(Debug, Clone, Default)]
+pub struct CargoOptions {
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    /// Custom profile, takes precedence over the build type
+    pub profile: Option<String>,
+    pub locked: bool,
+    pub offline: bool,
+    /// Target triple to build for
+    pub target: Option<String>,
+    /// Arguments passed to cargo as is
+    pub extra_args: Vec<String>,
+}
+
+impl CargoOptions {
+    fn args(&self, build_type: &BuildType) -> Vec<String> {
+        let mut args = vec![];
+        match (&self.profile, build_type) {
+            (Some(profile), _) => args.extend(["--profile".to_string(), profile.clone()]),
+            (None, BuildType::Release) => args.push("--release".to_string()),
+            (None, BuildType::Debug) => (),
+        }
+        if !self.features.is_empty() {
+            args.extend(["--features".to_string(), self.features.join(",")]);
+        }
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        if self.locked {
+            args.push("--locked".to_string());
+        }
+        if self.offline {
+            args.push("--offline".to_string());
+        }
+        if let Some(target) = &self.target {
+            args.extend(["--target".to_string(), target.clone()]);
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+/// Directory where cargo puts artifacts of the build,
+/// e.g. `target/debug` or `target/x86_64-unknown-linux-gnu/custom`.
+pub fn artifact_dir(target_dir: &Path, build_type: &BuildType, options: &CargoOptions) -> PathBuf {
+    // Built-in profiles do not match their directories
+    let profile_dir = match (options.profile.as_deref(), build_type) {
+        (Some("dev" | "test"), _) | (None, BuildType::Debug) => "debug",
+        (Some("release" | "bench"), _) | (None, BuildType::Release) => "release",
+        (Some(profile), _) => profile,
+    };
+
+    match &options.target {
+        Some(target) => target_dir.join(target).join(profile_dir),
+        None => target_dir.join(profile_dir),
     }
+}
 
+#[allow(clippy::needless_pass_by_value)]
+pub fn cargo_build(
+    build_type: BuildType,
+    target_dir: &PathBuf,
+    build_dir: &PathBuf,
+    options: &CargoOptions,
+) -> Result<()> {
     let mut child = Command::new("cargo")
-        .args(args)
+        .arg("build")
+        .args(options.args(&build_type))
         .arg("--target-dir")
         .arg(target_dir)
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .current_dir(build_dir)
         .spawn()
-        .context("running cargo build")?;
+        .map_err(|e| coded(ErrorCode::BuildFailed, format!("running cargo build: {e}")))?;
+
+    // Stream cargo progress live and keep it for the error message
+    let stderr = child.stderr.take().expect("Failed to capture stderr");
+    let stderr_thread = thread::spawn(move || {
+        let mut output = String::new();
+        for line in BufReader::new(stderr).lines() {
+            let line = line.unwrap_or_else(|e| format!("{e}"));
+            eprintln!("{line}");
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output
+    });
 
+    // Stdout is reserved for events in JSON mode
     let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let reader = BufReader::new(stdout);
-    for line in reader.lines() {
+    for line in BufReader::new(stdout).lines() {
         let line = line.unwrap_or_else(|e| format!("{e}"));
-        print!("{line}");
+        if output::is_json() {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
     }
 
-    if !child.wait().unwrap().success() {
-        let mut stderr = String::new();
-        child.stderr.unwrap().read_to_string(&mut stderr).unwrap();
-        bail!("build error: {stderr}");
+    let status = child.wait()?;
+    let stderr = stderr_thread.join().unwrap_or_default();
+    if !status.success() {
+        // Cargo output is already printed to the terminal
+        let message = if output::is_json() {
+            format!("build error: {stderr}")
+        } else {
+            format!("build error: cargo build {status}")
+        };
+        return Err(coded(ErrorCode::BuildFailed, message));
     }
 
     Ok(())
@@ -85,3 +176,47 @@ pub fn check_running_instances(data_dir: &Path, plugin_path: &Path) -> Result<Op
 
     Ok(None)
 }
+
+// Send raw input into `picodata admin` connected to the given socket
+// and return everything it printed to stdout
+pub fn picodata_admin_exec(
+    picodata_path: &Path,
+    socket_path: &Path,
+    input: &str,
+) -> Result<String> {
+    let mut picodata_admin = Command::new(picodata_path)
+        .arg("admin")
+        .arg(
+            socket_path
+                .to_str()
+                .context("path to picodata admin socket contains invalid characters")?,
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn child proccess of picodata admin")?;
+
+    {
+        let picodata_stdin = picodata_admin
+            .stdin
+            .as_mut()
+            .context("failed to get picodata stdin")?;
+        picodata_stdin
+            .write_all(input.as_bytes())
+            .context("failed to send input into picodata admin")?;
+    }
+
+    let output = picodata_admin
+        .wait_with_output()
+        .context("failed to wait for picodata admin")?;
+
+    if !output.status.success() {
+        bail!(
+            "picodata admin failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
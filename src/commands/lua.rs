@@ -0,0 +1,247 @@
+use anyhow::{bail, Context, Result};
+use derive_builder::Builder;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::lib::{get_active_socket_path, picodata_admin_exec};
+
+const GET_TIER_LUA: &str = "pico.instance_info().tier";
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+/// Instances of the running cluster on which Lua code is evaluated.
+#[derive(Debug, Clone, Default)]
+pub enum Target {
+    /// Instances with given names, e.g. `default_1_1` or `i1`
+    Instances(Vec<String>),
+    /// Every instance of the given tier
+    Tier(String),
+    /// Every instance of the cluster
+    #[default]
+    All,
+}
+
+#[derive(Debug, Clone)]
+pub enum LuaSource {
+    Code(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, Builder)]
+pub struct Params {
+    #[builder(setter(custom))]
+    source: LuaSource,
+    #[builder(default)]
+    target: Target,
+    #[builder(default)]
+    output_format: OutputFormat,
+    #[builder(default = "PathBuf::from(\"./tmp\")")]
+    data_dir: PathBuf,
+    #[builder(default = "PathBuf::from(\"./\")")]
+    plugin_path: PathBuf,
+    #[builder(default = "PathBuf::from(\"picodata\")")]
+    picodata_path: PathBuf,
+}
+
+impl ParamsBuilder {
+    pub fn code(&mut self, code: String) -> &mut Self {
+        self.source = Some(LuaSource::Code(code));
+        self
+    }
+
+    pub fn file(&mut self, path: PathBuf) -> &mut Self {
+        self.source = Some(LuaSource::File(path));
+        self
+    }
+}
+
+// Quote arbitrary text as a single line Lua string literal
+pub(crate) fn quote_lua_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Admin console evaluates input line by line, so the whole chunk is passed
+// as one string. Like the console itself, try to evaluate it as an expression first
+fn wrap_lua_chunk(code: &str) -> String {
+    format!(
+        "local chunk = {}; \
+         local f, err = load('return ' .. chunk); \
+         if f == nil then f, err = load(chunk) end; \
+         if f == nil then error(err) end; \
+         return f()",
+        quote_lua_string(code)
+    )
+}
+
+// Extract the last YAML document printed by the admin console
+fn parse_console_output(output: &str) -> Result<Value> {
+    let lines: Vec<&str> = output.lines().collect();
+    let Some(start) = lines.iter().rposition(|l| l.trim_end() == "---") else {
+        bail!("unexpected picodata admin output: {output}");
+    };
+    let end = lines[start..]
+        .iter()
+        .position(|l| l.trim_end() == "...")
+        .map_or(lines.len(), |pos| start + pos);
+
+    let document = lines[start..end].join("\n");
+    let values: Vec<Value> = serde_yaml::from_str(&document)
+        .context(format!("failed to parse picodata admin output: {document}"))?;
+
+    // Console reports errors as a single `error` field
+    if let [Value::Mapping(map)] = values.as_slice() {
+        if map.len() == 1 {
+            if let Some(err) = map.get("error") {
+                bail!(
+                    "lua error: {}",
+                    err.as_str()
+                        .map_or_else(|| format!("{err:?}"), str::to_string)
+                );
+            }
+        }
+    }
+
+    Ok(match values.len() {
+        0 => Value::Null,
+        1 => values.into_iter().next().unwrap(),
+        _ => Value::Sequence(values),
+    })
+}
+
+pub(crate) fn eval_on_socket(
+    picodata_path: &Path,
+    socket_path: &Path,
+    code: &str,
+) -> Result<Value> {
+    let input = format!("\\lua\n{}\n", wrap_lua_chunk(code));
+    let output = picodata_admin_exec(picodata_path, socket_path, &input)?;
+    parse_console_output(&output)
+}
+
+// Instance names are stored as symlinks to the instance data dirs
+fn list_instances(cluster_dir: &Path) -> Result<Vec<String>> {
+    let dirs = fs::read_dir(cluster_dir).context(format!(
+        "cluster data dir with path {} does not exist",
+        cluster_dir.display()
+    ))?;
+
+    let mut instances = vec![];
+    for entry in dirs {
+        let entry = entry?;
+        if !entry.file_type()?.is_symlink() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str() {
+            instances.push(name.to_string());
+        }
+    }
+    instances.sort();
+
+    Ok(instances)
+}
+
+fn resolve_targets(params: &Params) -> Result<Vec<(String, PathBuf)>> {
+    let cluster_dir = params.plugin_path.join(&params.data_dir).join("cluster");
+
+    let names = match &params.target {
+        Target::Instances(names) => names.clone(),
+        Target::Tier(_) | Target::All => list_instances(&cluster_dir)?,
+    };
+
+    let mut targets = vec![];
+    for name in names {
+        let Some(socket_path) =
+            get_active_socket_path(&params.data_dir, &params.plugin_path, &name)
+        else {
+            if let Target::Instances(_) = params.target {
+                bail!("instance {name} is not running");
+            }
+            log::warn!("instance {name} is not running - skipping");
+            continue;
+        };
+        let socket_path = PathBuf::from(socket_path);
+
+        if let Target::Tier(tier) = &params.target {
+            let instance_tier = eval_on_socket(&params.picodata_path, &socket_path, GET_TIER_LUA)
+                .context(format!("failed to get tier of instance {name}"))?;
+            if instance_tier.as_str() != Some(tier.as_str()) {
+                continue;
+            }
+        }
+
+        targets.push((name, socket_path));
+    }
+
+    if targets.is_empty() {
+        bail!("no running instances matching the target were found");
+    }
+
+    Ok(targets)
+}
+
+/// Evaluate Lua code on the targeted instances and return results by instance name.
+pub fn eval(params: &Params) -> Result<BTreeMap<String, Result<Value>>> {
+    let code = match &params.source {
+        LuaSource::Code(code) => code.clone(),
+        LuaSource::File(path) => fs::read_to_string(path)
+            .context(format!("failed to read Lua file {}", path.display()))?,
+    };
+
+    let mut results = BTreeMap::new();
+    for (name, socket_path) in resolve_targets(params)? {
+        let result = eval_on_socket(&params.picodata_path, &socket_path, &code);
+        results.insert(name, result);
+    }
+
+    Ok(results)
+}
+
+pub fn cmd(params: &Params) -> Result<()> {
+    let results = eval(params)?;
+
+    let mut failed = vec![];
+    let mut output = BTreeMap::new();
+    for (name, result) in results {
+        let value = match result {
+            Ok(value) => value,
+            Err(e) => {
+                failed.push(name.clone());
+                let mut error = serde_yaml::Mapping::new();
+                error.insert("error".into(), format!("{e:#}").into());
+                Value::Mapping(error)
+            }
+        };
+        output.insert(name, value);
+    }
+
+    match params.output_format {
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&output)?),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&output)?),
+    }
+
+    if !failed.is_empty() {
+        bail!("failed to evaluate Lua on instances: {}", failed.join(", "));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::run::MigrationContextVar;
+
+pub const MIGRATIONS_DIR: &str = "migrations";
+pub const MIGRATIONS_LOCK_NAME: &str = "migrations.lock";
+
+/// Prefix of `migration_context` placeholders in migrations, e.g. `@_plugin_config.table_name`
+const CONTEXT_PLACEHOLDER: &str = "@_plugin_config.";
+
+const LOCK_HEADER: &str = "\
+# Checksums of released migrations, maintained by `cargo pike plugin pack --lock-migrations`.
+# Locked migrations must not be changed, add a new migration instead.
+";
+
+pub(crate) fn checksum(path: &Path) -> Result<String> {
+    let data = fs::read(path).context(format!("failed to read {}", path.display()))?;
+    Ok(Sha256::digest(data)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        }))
+}
+
+/// `migrations.lock` of the plugin, file names of released migrations with their checksums.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MigrationsLock {
+    #[serde(default)]
+    pub(crate) migrations: BTreeMap<String, String>,
+}
+
+impl MigrationsLock {
+    pub fn load(package_dir: &Path) -> Result<Self> {
+        let lock_path = package_dir.join(MIGRATIONS_LOCK_NAME);
+        if !lock_path.exists() {
+            return Ok(Self::default());
+        }
+
+        toml::from_str(
+            &fs::read_to_string(&lock_path)
+                .context(format!("failed to read {}", lock_path.display()))?,
+        )
+        .context(format!("failed to parse {}", lock_path.display()))
+    }
+
+    fn save(&self, package_dir: &Path) -> Result<()> {
+        let lock_path = package_dir.join(MIGRATIONS_LOCK_NAME);
+        let content = format!("{LOCK_HEADER}\n{}", toml::to_string(self)?);
+        fs::write(&lock_path, content).context(format!("failed to write {}", lock_path.display()))
+    }
+
+    /// Add checksums of migrations, which are not locked yet.
+    /// Returns file names of the newly locked migrations.
+    pub fn lock(package_dir: &Path) -> Result<Vec<String>> {
+        let mut lock = Self::load(package_dir)?;
+        let mut locked = vec![];
+        for file_name in migration_files(package_dir)? {
+            if lock.migrations.contains_key(&file_name) {
+                continue;
+            }
+            let path = package_dir.join(MIGRATIONS_DIR).join(&file_name);
+            lock.migrations.insert(file_name.clone(), checksum(&path)?);
+            locked.push(file_name);
+        }
+
+        if !locked.is_empty() {
+            lock.save(package_dir)?;
+        }
+
+        Ok(locked)
+    }
+}
+
+// File names in the migrations directory, sorted
+pub(crate) fn migration_files(package_dir: &Path) -> Result<Vec<String>> {
+    let migrations_dir = package_dir.join(MIGRATIONS_DIR);
+    if !migrations_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(&migrations_dir)
+        .context(format!("failed to read {}", migrations_dir.display()))?
+    {
+        files.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Mismatches of `migration_context` placeholders in migrations with the given variables.
+#[derive(Debug, Default)]
+pub struct ContextLint {
+    /// Placeholders without a value, migrations fail to apply with them
+    pub missing: Vec<String>,
+    /// Variables not used by any migration
+    pub unused: Vec<String>,
+}
+
+// Names of `migration_context` variables used in the migration
+fn context_placeholders(content: &str) -> BTreeSet<&str> {
+    content
+        .match_indices(CONTEXT_PLACEHOLDER)
+        .filter_map(|(start, _)| {
+            let rest = &content[start + CONTEXT_PLACEHOLDER.len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (end > 0).then(|| &rest[..end])
+        })
+        .collect()
+}
+
+/// Cross-check `migration_context` placeholders of the plugin migrations
+/// with the variables set for the plugin, e.g. in `topology.toml`.
+pub fn check_context(package_dir: &Path, vars: &[MigrationContextVar]) -> Result<ContextLint> {
+    let migrations_dir = package_dir.join(MIGRATIONS_DIR);
+    let provided: BTreeSet<&str> = vars.iter().map(|var| var.name.as_str()).collect();
+    let mut used = BTreeSet::new();
+    let mut lint = ContextLint::default();
+
+    for file_name in migration_files(package_dir)? {
+        let path = migrations_dir.join(&file_name);
+        if !path.is_file() {
+            continue;
+        }
+
+        let content =
+            fs::read_to_string(&path).context(format!("failed to read {}", path.display()))?;
+        for name in context_placeholders(&content) {
+            if !provided.contains(name) {
+                lint.missing.push(format!(
+                    "{}: migration context variable `{name}` has no value",
+                    path.display()
+                ));
+            }
+            used.insert(name.to_string());
+        }
+    }
+
+    // Plugin without context placeholders in migrations, like a freshly
+    // generated one, may keep variables for migrations it does not have yet
+    if used.is_empty() {
+        return Ok(lint);
+    }
+    lint.unused = provided
+        .into_iter()
+        .filter(|name| !used.contains(*name))
+        .map(|name| format!("migration context variable `{name}` is not used by any migration"))
+        .collect();
+
+    Ok(lint)
+}
@@ -1,7 +1,12 @@
 pub(crate) mod clean;
 pub(crate) mod config;
 pub(crate) mod enter;
+pub(crate) mod instance_log;
 pub(crate) mod lib;
+pub(crate) mod lua;
+pub(crate) mod migrations;
+pub(crate) mod output;
 pub(crate) mod plugin;
+pub(crate) mod project;
 pub(crate) mod run;
 pub(crate) mod stop;
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+static MESSAGE_FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Human readable log lines
+    #[default]
+    Human,
+    /// One JSON event per line on stdout, logs go to stderr
+    Json,
+}
+
+pub fn set_message_format(format: MessageFormat) {
+    let _ = MESSAGE_FORMAT.set(format);
+}
+
+pub fn message_format() -> MessageFormat {
+    MESSAGE_FORMAT.get().copied().unwrap_or_default()
+}
+
+pub fn is_json() -> bool {
+    message_format() == MessageFormat::Json
+}
+
+/// Stable codes of errors reported in JSON mode.
+///
+/// Codes are part of the public interface, do not rename them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Command failed for a reason without a dedicated code
+    CommandFailed,
+    /// Pike was started outside of the plugin directory
+    PluginDirNotFound,
+    /// Data directory to clean does not exist
+    NothingToClean,
+    /// Picodata binary could not be found
+    PicodataNotFound,
+    /// Another cluster is already running in the data directory
+    ClusterAlreadyRunning,
+    /// Cluster data directory does not exist
+    ClusterNotFound,
+    /// `cargo build` of the plugin failed
+    BuildFailed,
+    /// Topology file could not be read or parsed
+    InvalidTopology,
+    /// Plugins could not be installed or enabled on the cluster
+    PluginInstallFailed,
+    /// Service configuration could not be applied
+    ConfigApplyFailed,
+    /// Custom config path was used in a workspace without `--plugin-name`
+    PluginNameRequired,
+    /// Path of the new plugin already exists
+    PathAlreadyExists,
+    /// Cluster instances did not become online in time
+    ClusterNotReady,
+    /// Plugin archive failed verification
+    InvalidArchive,
+    /// Plugin config does not match default configuration of services in the manifest
+    InvalidConfig,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = serde_json::to_value(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", code.as_str().unwrap_or_default())
+    }
+}
+
+/// Error carrying a stable [`ErrorCode`], can be found in any `anyhow` chain.
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    message: String,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+pub fn coded(code: ErrorCode, message: impl Into<String>) -> anyhow::Error {
+    anyhow::Error::new(CodedError {
+        code,
+        message: message.into(),
+    })
+}
+
+pub fn error_code(err: &anyhow::Error) -> ErrorCode {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<CodedError>())
+        .map_or(ErrorCode::CommandFailed, |e| e.code)
+}
+
+/// Event without a typed payload.
+pub type Event<'a> = TypedEvent<'a, ()>;
+
+/// Event emitted in JSON mode, `T` is the type of the command specific payload.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TypedEvent<'a, T: ?Sized> {
+    InstanceStarted {
+        instance_name: &'a str,
+        instance_id: u16,
+        tier: &'a str,
+        bin_port: u16,
+        http_port: u16,
+        pg_port: u16,
+        data_dir: &'a Path,
+    },
+    PluginEnabled {
+        plugin: &'a str,
+        version: &'a str,
+    },
+    ClusterStarted {
+        total_instances: u16,
+        launch_time_sec: u64,
+    },
+    DaemonStarted {
+        supervisor_pid: u32,
+        pid_file: &'a Path,
+    },
+    InstanceStopped {
+        instance_name: &'a str,
+        pid: u32,
+        skipped: bool,
+    },
+    DataDirCleaned {
+        data_dir: &'a Path,
+    },
+    ArchiveCreated {
+        plugin: &'a str,
+        version: &'a str,
+        path: &'a Path,
+    },
+    DebuginfoCreated {
+        plugin: &'a str,
+        version: &'a str,
+        build_id: &'a str,
+        path: &'a Path,
+    },
+    BundleCreated {
+        plugins: &'a T,
+        path: &'a Path,
+    },
+    MigrationsLocked {
+        plugin: &'a str,
+        migrations: &'a [String],
+        path: &'a Path,
+    },
+    ArchiveVerified {
+        path: &'a Path,
+        problems: &'a [String],
+    },
+    ServiceConfigDiff {
+        plugin: &'a str,
+        version: &'a str,
+        service: &'a str,
+        diff: &'a T,
+        dry_run: bool,
+    },
+    PluginConfig {
+        plugin: &'a str,
+        version: &'a str,
+        config: &'a T,
+    },
+    PluginConfigSchema {
+        plugin: &'a str,
+        version: &'a str,
+        schema: &'a serde_json::Value,
+    },
+    ServiceConfigApplied {
+        plugin: &'a str,
+        version: &'a str,
+        service: &'a str,
+    },
+    PluginCreated {
+        name: &'a str,
+        path: &'a Path,
+    },
+    Error {
+        code: ErrorCode,
+        message: &'a str,
+    },
+}
+
+/// Print the event as a JSON line to stdout, does nothing in human mode.
+pub fn emit<T: Serialize + ?Sized>(event: &TypedEvent<'_, T>) {
+    if !is_json() {
+        return;
+    }
+
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{line}"),
+        Err(e) => log::error!("failed to serialize event: {e}"),
+    }
+}
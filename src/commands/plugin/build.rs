@@ -1,15 +1,22 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use lib::{cargo_build, BuildType};
+use lib::{cargo_build, BuildType, CargoOptions};
 
 use crate::commands::lib;
+use crate::commands::project::Project;
 
-pub fn cmd(release: bool, target_dir: &PathBuf, plugin_path: &PathBuf) -> Result<()> {
+pub fn cmd(
+    release: bool,
+    target_dir: Option<&Path>,
+    plugin_path: &PathBuf,
+    cargo_options: &CargoOptions,
+) -> Result<()> {
     let build_type = if release {
         BuildType::Release
     } else {
         BuildType::Debug
     };
-    cargo_build(build_type, target_dir, plugin_path).context("building of plugin")
+    let target_dir = Project::load(plugin_path)?.target_dir(target_dir);
+    cargo_build(build_type, &target_dir, plugin_path, cargo_options).context("building of plugin")
 }
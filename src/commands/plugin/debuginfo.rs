@@ -0,0 +1,103 @@
+use anyhow::{bail, Context, Result};
+use object::{BinaryFormat, Object};
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Stripped library and its debug file, linked by GNU build-id.
+pub(crate) struct SplitDebuginfo {
+    pub stripped_lib: PathBuf,
+    pub debug_file: PathBuf,
+    pub build_id: String,
+}
+
+impl SplitDebuginfo {
+    /// Path of the debug file in the `.build-id` layout, searched by gdb and debuginfod.
+    pub fn build_id_path(&self) -> PathBuf {
+        let (prefix, rest) = self.build_id.split_at(2);
+        Path::new(".build-id")
+            .join(prefix)
+            .join(format!("{rest}.debug"))
+    }
+}
+
+fn objcopy<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(args: I) -> Result<()> {
+    let program = env::var("OBJCOPY").unwrap_or_else(|_| "objcopy".to_string());
+    let output = Command::new(&program)
+        .args(args)
+        .output()
+        .context(format!("failed to run {program}, is binutils installed?"))?;
+    if !output.status.success() {
+        bail!(
+            "{program} {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Split debug info of the library into `out_dir`, the library itself is left intact.
+pub(crate) fn split(lib_path: &Path, out_dir: &Path) -> Result<SplitDebuginfo> {
+    let data = fs::read(lib_path).context(format!("failed to read {}", lib_path.display()))?;
+    let library = object::File::parse(data.as_slice())
+        .context(format!("failed to parse {}", lib_path.display()))?;
+    if library.format() != BinaryFormat::Elf {
+        bail!(
+            "splitting debug info is supported only for ELF libraries, {} is {:?}",
+            lib_path.display(),
+            library.format()
+        );
+    }
+
+    let Some(build_id) = library.build_id()? else {
+        bail!(
+            "{} has no GNU build-id, link it with `-C link-arg=-Wl,--build-id`",
+            lib_path.display()
+        );
+    };
+    let build_id = build_id.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+
+    if library.section_by_name(".debug_info").is_none() {
+        log::warn!(
+            "{} has no debug info, set `debug = true` in the cargo profile",
+            lib_path.display()
+        );
+    }
+
+    fs::create_dir_all(out_dir)
+        .context(format!("failed to create directory {}", out_dir.display()))?;
+    let lib_name = lib_path.file_name().context("library has no file name")?;
+    let stripped_lib = out_dir.join(lib_name);
+    let debug_file = out_dir.join(format!("{}.debug", lib_name.to_string_lossy()));
+
+    objcopy([
+        OsStr::new("--only-keep-debug"),
+        lib_path.as_os_str(),
+        debug_file.as_os_str(),
+    ])
+    .context("failed to extract debug info")?;
+
+    // Symbol table stays in the library for in-process backtraces
+    let debuglink = format!("--add-gnu-debuglink={}", debug_file.display());
+    objcopy([
+        OsStr::new("--strip-debug"),
+        OsStr::new(&debuglink),
+        lib_path.as_os_str(),
+        stripped_lib.as_os_str(),
+    ])
+    .context("failed to strip debug info")?;
+
+    Ok(SplitDebuginfo {
+        stripped_lib,
+        debug_file,
+        build_id,
+    })
+}
@@ -1,3 +1,5 @@
 pub(crate) mod build;
+pub(crate) mod debuginfo;
 pub(crate) mod new;
 pub(crate) mod pack;
+pub(crate) mod verify;
@@ -1,4 +1,4 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use fs_extra::{dir, file};
 use std::{
     env,
@@ -11,6 +11,8 @@ use std::{
 
 use include_dir::{include_dir, Dir, DirEntry};
 
+use crate::commands::output::{coded, emit, ErrorCode, Event};
+
 static PLUGIN_TEMPLATE: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/plugin_template");
 static WS_CARGO_MANIFEST_TEMPLATE: &str = r#"[workspace]
 resolver = "3"
@@ -144,7 +146,10 @@ pub fn cmd(path: Option<&Path>, without_git: bool, init_workspace: bool) -> Resu
     let path = match path {
         Some(p) => {
             if p.exists() {
-                bail!("path {} already exists", p.to_string_lossy())
+                return Err(coded(
+                    ErrorCode::PathAlreadyExists,
+                    format!("path {} already exists", p.to_string_lossy()),
+                ));
             };
             p.to_path_buf()
         }
@@ -184,5 +189,10 @@ pub fn cmd(path: Option<&Path>, without_git: bool, init_workspace: bool) -> Resu
             .context("failed to initiate workspace")?;
     }
 
+    emit(&Event::PluginCreated {
+        name: project_name,
+        path: &plugin_path,
+    });
+
     Ok(())
 }
@@ -1,151 +1,554 @@
 use anyhow::{bail, Context, Result};
+use derive_builder::Builder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use lib::{cargo_build, BuildType};
-use serde::Deserialize;
+use lib::{artifact_dir, cargo_build, BuildType, CargoOptions};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs::File;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::{env, fs};
-use tar::Builder;
-use toml::Value;
+use tar::{Builder as TarBuilder, EntryType, Header};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
 
 use crate::commands::lib;
-
-#[derive(Deserialize)]
-struct PackageInfo {
-    name: String,
-    version: String,
+use crate::commands::migrations::{MigrationsLock, MIGRATIONS_LOCK_NAME};
+use crate::commands::output::{self, emit, Event, TypedEvent};
+use crate::commands::plugin::debuginfo::{self, SplitDebuginfo};
+use crate::commands::project::{Package, Project};
+
+/// Index of plugins packed into a bundle archive.
+pub(crate) const BUNDLE_INDEX_NAME: &str = "bundle.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundlePlugin {
+    pub name: String,
+    pub version: String,
 }
 
-#[derive(Deserialize)]
-struct CargoManifest {
-    package: PackageInfo,
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub plugins: Vec<BundlePlugin>,
 }
 
 #[cfg(target_os = "linux")]
-const LIB_EXT: &str = "so";
+pub(crate) const LIB_EXT: &str = "so";
 
 #[cfg(target_os = "macos")]
-const LIB_EXT: &str = "dylib";
+pub(crate) const LIB_EXT: &str = "dylib";
+
+/// Format of the plugin archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArchiveFormat {
+    #[default]
+    #[value(name = "tar.gz")]
+    TarGz,
+    #[value(name = "tar.zst")]
+    TarZst,
+    #[value(name = "tar")]
+    Tar,
+    #[value(name = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Guess the format by the extension of the archive.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.zst") {
+            Some(Self::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+            Self::Tar => "tar",
+            Self::Zip => "zip",
+        }
+    }
+
+    // Default and allowed compression levels of the format
+    fn compression_levels(self) -> Option<(i32, RangeInclusive<i32>)> {
+        match self {
+            Self::TarGz | Self::Zip => Some((6, 0..=9)),
+            Self::TarZst => Some((3, 1..=22)),
+            Self::Tar => None,
+        }
+    }
+
+    fn compression_level(self, level: Option<i32>) -> Result<i32> {
+        let Some((default_level, levels)) = self.compression_levels() else {
+            if level.is_some() {
+                log::warn!(
+                    "{} archives are not compressed, ignoring compression level",
+                    self.extension()
+                );
+            }
+            return Ok(0);
+        };
+
+        let level = level.unwrap_or(default_level);
+        if !levels.contains(&level) {
+            bail!(
+                "compression level {level} is not supported by {}, expected value in range {}..={}",
+                self.extension(),
+                levels.start(),
+                levels.end()
+            );
+        }
+
+        Ok(level)
+    }
+}
+
+#[derive(Debug, Builder)]
+pub struct Params {
+    #[builder(default)]
+    debug: bool,
+    /// Target directory configured for cargo if not set
+    #[builder(default, setter(into))]
+    target_dir: Option<PathBuf>,
+    #[builder(default = "PathBuf::from(\"./\")")]
+    plugin_path: PathBuf,
+    /// Path of the archive, or directory for archives of workspace plugins
+    #[builder(default)]
+    output: Option<PathBuf>,
+    #[builder(default)]
+    format: ArchiveFormat,
+    #[builder(default)]
+    compression_level: Option<i32>,
+    #[builder(default)]
+    cargo_options: CargoOptions,
+    /// Workspace plugins to pack, all plugins if empty
+    #[builder(default)]
+    packages: Vec<String>,
+    /// Pack selected plugins into a single archive
+    #[builder(default)]
+    bundle: bool,
+    /// Strip packed libraries and write debug info into separate archives
+    #[builder(default)]
+    split_debuginfo: bool,
+    /// Record checksums of the packed migrations in `migrations.lock`
+    #[builder(default)]
+    lock_migrations: bool,
+}
+
+pub fn cmd(params: &Params) -> Result<()> {
+    // Fail before the long build
+    params.format.compression_level(params.compression_level)?;
+
+    let project = Project::load(&params.plugin_path)?;
 
-pub fn cmd(pack_debug: bool, target_dir: &PathBuf, pluging_path: &PathBuf) -> Result<()> {
-    let root_dir = env::current_dir()?.join(pluging_path);
+    let plugins: Vec<&Package> = project
+        .plugins()
+        .filter(|plugin| params.packages.is_empty() || params.packages.contains(&plugin.name))
+        .collect();
 
-    let build_dir = if pack_debug {
-        cargo_build(BuildType::Debug, target_dir, pluging_path)
-            .context("building release version of plugin")?;
-        Path::new(&root_dir).join(target_dir).join("debug")
+    for package in &params.packages {
+        if !plugins.iter().any(|plugin| &plugin.name == package) {
+            bail!("package {package} is not a plugin of the project");
+        }
+    }
+
+    let build_type = if params.debug {
+        BuildType::Debug
     } else {
-        cargo_build(BuildType::Release, target_dir, pluging_path)
-            .context("building debug version of plugin")?;
-        Path::new(&root_dir).join(target_dir).join("release")
+        BuildType::Release
     };
+    let target_dir = project.target_dir(params.target_dir.as_deref());
+    let build_dir = artifact_dir(&target_dir, &build_type, &params.cargo_options);
+    let mut cargo_options = params.cargo_options.clone();
+    for package in &params.packages {
+        cargo_options
+            .extra_args
+            .push(format!("--package={package}"));
+    }
+    cargo_build(build_type, &target_dir, &params.plugin_path, &cargo_options)
+        .context("building plugin")?;
+
+    // Several archives can only be written into a directory
+    let single_archive = params.bundle || plugins.len() == 1;
+    let output = params.output.as_deref().map(|output| {
+        if !single_archive
+            || output.is_dir()
+            || output.as_os_str().as_encoded_bytes().ends_with(b"/")
+        {
+            ArchiveOutput::Dir(output)
+        } else {
+            ArchiveOutput::File(output)
+        }
+    });
+
+    if params.bundle {
+        let mut entries = ArchiveEntries::new();
+        let mut index = BundleIndex::default();
+        let mut debuginfos = vec![];
+        for plugin in &plugins {
+            let debuginfo =
+                collect_plugin_entries(&build_dir, plugin, params.split_debuginfo, &mut entries)?;
+            debuginfos.extend(debuginfo.map(|debuginfo| (plugin, debuginfo)));
+            index.plugins.push(BundlePlugin {
+                name: plugin.name.clone(),
+                version: plugin.version.clone(),
+            });
+        }
+        entries.insert(
+            PathBuf::from(BUNDLE_INDEX_NAME),
+            EntrySource::Data(serde_json::to_vec_pretty(&index)?).into(),
+        );
 
-    let plugin_dir = root_dir.clone();
+        let bundle_name = project
+            .path
+            .file_name()
+            .map_or_else(|| "plugins".into(), |name| name.to_string_lossy());
+        let archive_name = format!("{bundle_name}-bundle.{}", params.format.extension());
+        let archive_path = output_path(&build_dir, archive_name, output.as_ref());
+        write_archive(
+            &archive_path,
+            &entries,
+            params.format,
+            params.compression_level,
+        )?;
 
-    let cargo_toml_path = root_dir.join("Cargo.toml");
-    let cargo_toml_content = fs::read_to_string(&cargo_toml_path).context(format!(
-        "Failed to read Cargo.toml in {}",
-        &cargo_toml_path.display()
-    ))?;
+        emit(&TypedEvent::BundleCreated {
+            plugins: &index.plugins,
+            path: &archive_path,
+        });
+        if !output::is_json() {
+            println!("{}", archive_path.display());
+        }
 
-    let parsed_toml: Value = cargo_toml_content
-        .parse()
-        .context("Failed to parse Cargo.toml")?;
-
-    if let Some(workspace) = parsed_toml.get("workspace") {
-        if let Some(members) = workspace.get("members") {
-            if let Some(members_array) = members.as_array() {
-                for member in members_array {
-                    let member_str = member.as_str();
-                    if member_str.is_none() {
-                        continue;
-                    }
-
-                    if !root_dir
-                        .join(member_str.unwrap())
-                        .join("manifest.yaml.template")
-                        .exists()
-                    {
-                        continue;
-                    }
-
-                    create_plugin_archive(&build_dir, &root_dir.join(member_str.unwrap()))?;
-                }
-            }
+        for (plugin, debuginfo) in debuginfos {
+            write_debug_archive(&build_dir, output.as_ref(), plugin, &debuginfo)?;
+        }
+
+        if params.lock_migrations {
+            lock_migrations(&plugins)?;
         }
 
         return Ok(());
     }
 
-    create_plugin_archive(&build_dir, &plugin_dir)
+    for plugin in &plugins {
+        let mut entries = ArchiveEntries::new();
+        let debuginfo =
+            collect_plugin_entries(&build_dir, plugin, params.split_debuginfo, &mut entries)?;
+
+        let package_name = &plugin.name;
+        let version = &plugin.version;
+        let archive_name = format!("{package_name}-{version}.{}", params.format.extension());
+        let archive_path = output_path(&build_dir, archive_name, output.as_ref());
+        write_archive(
+            &archive_path,
+            &entries,
+            params.format,
+            params.compression_level,
+        )?;
+
+        emit(&Event::ArchiveCreated {
+            plugin: package_name,
+            version,
+            path: &archive_path,
+        });
+        if !output::is_json() {
+            println!("{}", archive_path.display());
+        }
+
+        if let Some(debuginfo) = debuginfo {
+            write_debug_archive(&build_dir, output.as_ref(), plugin, &debuginfo)?;
+        }
+    }
+
+    if params.lock_migrations {
+        lock_migrations(&plugins)?;
+    }
+
+    Ok(())
 }
 
-fn create_plugin_archive(build_dir: &Path, plugin_dir: &Path) -> Result<()> {
-    let plugin_version = get_latest_plugin_version(plugin_dir)?;
+// Packed migrations are released, build fails if they are changed afterwards
+fn lock_migrations(plugins: &[&Package]) -> Result<()> {
+    for plugin in plugins {
+        let locked = MigrationsLock::lock(&plugin.dir)
+            .context(format!("failed to lock migrations of {}", plugin.name))?;
+        for migration in &locked {
+            log::info!("Locked migration {migration} of {}", plugin.name);
+        }
 
-    let cargo_manifest: CargoManifest = toml::from_str(
-        &fs::read_to_string(plugin_dir.join("Cargo.toml")).context("failed to read Cargo.toml")?,
-    )
-    .context("failed to parse Cargo.toml")?;
+        emit(&Event::MigrationsLocked {
+            plugin: &plugin.name,
+            migrations: &locked,
+            path: &plugin.dir.join(MIGRATIONS_LOCK_NAME),
+        });
+    }
 
-    let package_name = cargo_manifest.package.name;
-    let normalized_package_name = package_name.replace('-', "_");
+    Ok(())
+}
 
-    let plugin_build_dir = build_dir.join(&package_name).join(&plugin_version);
+enum ArchiveOutput<'a> {
+    Dir(&'a Path),
+    File(&'a Path),
+}
 
-    let root_in_zip = Path::new(&package_name).join(plugin_version);
+fn output_path(build_dir: &Path, archive_name: String, output: Option<&ArchiveOutput>) -> PathBuf {
+    match output {
+        None => build_dir.join(archive_name),
+        Some(ArchiveOutput::Dir(dir)) => dir.join(archive_name),
+        Some(ArchiveOutput::File(path)) => path.to_path_buf(),
+    }
+}
 
-    let compressed_file = File::create(format!(
-        "{}/{package_name}-{}.tar.gz",
-        build_dir.display(),
-        cargo_manifest.package.version
-    ))
-    .context("failed to pack the plugin")?;
+// Collect files of the plugin under `<name>/<version>/`
+fn collect_plugin_entries(
+    build_dir: &Path,
+    plugin: &Package,
+    split_debuginfo: bool,
+    entries: &mut ArchiveEntries,
+) -> Result<Option<SplitDebuginfo>> {
+    let package_name = &plugin.name;
+    let plugin_build_dir = build_dir.join(package_name).join(&plugin.version);
+
+    let root_in_zip = Path::new(package_name).join(&plugin.version);
+
+    let lib_name = plugin.lib_file_name(LIB_EXT);
+
+    let mut plugin_entries = ArchiveEntries::new();
+
+    let lib_path = plugin_build_dir.join(&lib_name);
+    let debuginfo = if split_debuginfo {
+        let debuginfo =
+            debuginfo::split(&lib_path, &build_dir.join("debuginfo").join(package_name))
+                .context(format!("failed to split debug info of {package_name}"))?;
+        plugin_entries.insert(
+            root_in_zip.join(&lib_name),
+            EntrySource::Path(debuginfo.stripped_lib.clone()).into(),
+        );
+        Some(debuginfo)
+    } else {
+        collect_if_exists(&root_in_zip, &lib_path, &mut plugin_entries)?;
+        None
+    };
+    collect_if_exists(
+        &root_in_zip,
+        &plugin_build_dir.join("manifest.yaml"),
+        &mut plugin_entries,
+    )?;
+    collect_if_exists(
+        &root_in_zip,
+        &plugin_build_dir.join("migrations"),
+        &mut plugin_entries,
+    )?;
+    collect_if_exists(
+        &root_in_zip,
+        &plugin_build_dir.join("BUILD_INFO.json"),
+        &mut plugin_entries,
+    )?;
+
+    // Assets, including files from `include`, are flattened into the plugin root
+    // and must not replace built artifacts
+    let mut asset_entries = ArchiveEntries::new();
+    let assets_path = &plugin_build_dir.join("assets");
+    // no need to notify user if there is no assets folder
+    if assets_path.exists() {
+        for entry in fs::read_dir(assets_path)? {
+            let entry = entry?;
+            let entry_name = entry.file_name();
+            collect_if_exists(
+                &root_in_zip,
+                &assets_path.join(entry_name),
+                &mut asset_entries,
+            )?;
+        }
+    }
+    for (archived_path, entry) in asset_entries {
+        if plugin_entries.contains_key(&archived_path) {
+            bail!(
+                "asset {} of {package_name} collides with a built artifact, rename the asset",
+                archived_path.display()
+            );
+        }
+        plugin_entries.insert(archived_path, entry);
+    }
 
-    let mut encoder = GzEncoder::new(compressed_file, Compression::best());
+    // Apply `[package.metadata.pike.pack]` of the plugin
+    for (archived_path, mut entry) in plugin_entries {
+        let relative_path = archived_path
+            .strip_prefix(&root_in_zip)
+            .expect("plugin entries are collected under the plugin root");
+        if plugin.pack.is_excluded(relative_path) {
+            continue;
+        }
 
-    let lib_name = format!("lib{normalized_package_name}.{LIB_EXT}");
+        let relative_path = plugin.pack.renamed(relative_path);
+        entry.mode = plugin.pack.mode(&relative_path);
 
-    {
-        let mut tarball = Builder::new(&mut encoder);
+        let archived_path = root_in_zip.join(&relative_path);
+        if entries.contains_key(&archived_path) {
+            bail!(
+                "{} is packed twice, check renames of {package_name}",
+                archived_path.display()
+            );
+        }
+        entries.insert(archived_path, entry);
+    }
 
-        archive_if_exists(
-            &root_in_zip,
-            &plugin_build_dir.join(&lib_name),
-            &mut tarball,
-        )?;
-        archive_if_exists(
-            &root_in_zip,
-            &plugin_build_dir.join("manifest.yaml"),
-            &mut tarball,
-        )?;
-        archive_if_exists(
-            &root_in_zip,
-            &plugin_build_dir.join("migrations"),
-            &mut tarball,
-        )?;
+    Ok(debuginfo)
+}
 
-        let assets_path = &plugin_build_dir.join("assets");
-        // no need to notify user if there is no assets folder
-        if assets_path.exists() {
-            for entry in fs::read_dir(assets_path)? {
-                let entry = entry?;
-                let entry_name = entry.file_name();
-                archive_if_exists(&root_in_zip, &assets_path.join(entry_name), &mut tarball)?;
-            }
+// Debug info goes next to the plugin archive as `<name>-<version>-debug.tar.gz`
+fn write_debug_archive(
+    build_dir: &Path,
+    output: Option<&ArchiveOutput>,
+    package: &Package,
+    debuginfo: &SplitDebuginfo,
+) -> Result<()> {
+    let mut entries = ArchiveEntries::new();
+    entries.insert(
+        debuginfo.build_id_path(),
+        EntrySource::Path(debuginfo.debug_file.clone()).into(),
+    );
+
+    let archive_name = format!("{}-{}-debug.tar.gz", package.name, package.version);
+    let archive_path = match output {
+        Some(ArchiveOutput::File(path)) => path.with_file_name(archive_name),
+        output => output_path(build_dir, archive_name, output),
+    };
+    write_archive(&archive_path, &entries, ArchiveFormat::TarGz, None)?;
+
+    emit(&Event::DebuginfoCreated {
+        plugin: &package.name,
+        version: &package.version,
+        build_id: &debuginfo.build_id,
+        path: &archive_path,
+    });
+    if !output::is_json() {
+        println!("{}", archive_path.display());
+    }
+
+    Ok(())
+}
+
+fn write_archive(
+    archive_path: &Path,
+    entries: &ArchiveEntries,
+    format: ArchiveFormat,
+    compression_level: Option<i32>,
+) -> Result<()> {
+    if let Some(parent) = archive_path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("failed to create directory {}", parent.display()))?;
+    }
+
+    let mtime = source_date_epoch()?;
+    let level = format.compression_level(compression_level)?;
+    let archive_file = File::create(archive_path).context("failed to pack the plugin")?;
+    match format {
+        ArchiveFormat::Tar => {
+            write_tar(archive_file, entries, mtime)?;
+        }
+        ArchiveFormat::TarGz => {
+            let encoder = GzEncoder::new(archive_file, Compression::new(level.unsigned_abs()));
+            write_tar(encoder, entries, mtime)?.finish()?;
         }
+        ArchiveFormat::TarZst => {
+            let encoder = zstd::Encoder::new(archive_file, level)?;
+            write_tar(encoder, entries, mtime)?.finish()?;
+        }
+        ArchiveFormat::Zip => write_zip(archive_file, entries, mtime, level)?,
     }
 
-    encoder.finish()?;
+    write_checksum(archive_path)?;
 
     Ok(())
 }
 
-fn archive_if_exists(
+// Files to pack by their path inside the archive,
+// sorted to make archives reproducible
+type ArchiveEntries = BTreeMap<PathBuf, ArchiveEntry>;
+
+struct ArchiveEntry {
+    source: EntrySource,
+    /// Mode of the file set by the pack config
+    mode: Option<u32>,
+}
+
+impl From<EntrySource> for ArchiveEntry {
+    fn from(source: EntrySource) -> Self {
+        Self { source, mode: None }
+    }
+}
+
+impl ArchiveEntry {
+    fn kind(&self) -> Result<(bool, u32)> {
+        let (is_dir, mode) = self.source.kind()?;
+        if is_dir {
+            return Ok((is_dir, mode));
+        }
+        Ok((is_dir, self.mode.unwrap_or(mode)))
+    }
+}
+
+enum EntrySource {
+    /// File or directory on disk
+    Path(PathBuf),
+    /// Generated file contents
+    Data(Vec<u8>),
+}
+
+impl EntrySource {
+    fn description(&self) -> String {
+        match self {
+            Self::Path(path) => path.display().to_string(),
+            Self::Data(_) => "generated file".to_string(),
+        }
+    }
+
+    // Directory flag and normalized mode of the entry
+    fn kind(&self) -> Result<(bool, u32)> {
+        let Self::Path(path) = self else {
+            return Ok((false, 0o644));
+        };
+        let metadata =
+            fs::metadata(path).context(format!("failed to read metadata of {}", path.display()))?;
+        if metadata.is_dir() {
+            return Ok((true, 0o755));
+        }
+        let is_executable = metadata.permissions().mode() & 0o111 != 0;
+        Ok((false, if is_executable { 0o755 } else { 0o644 }))
+    }
+
+    fn open(&self) -> Result<(Box<dyn io::Read + '_>, u64)> {
+        match self {
+            Self::Path(path) => {
+                let file =
+                    File::open(path).context(format!("failed to open file {}", path.display()))?;
+                let len = file.metadata()?.len();
+                Ok((Box::new(file), len))
+            }
+            Self::Data(data) => Ok((Box::new(data.as_slice()), data.len() as u64)),
+        }
+    }
+}
+
+fn collect_if_exists(
     root_in_zip: &Path,
     file_path: &Path,
-    tarball: &mut Builder<&mut GzEncoder<File>>,
+    entries: &mut ArchiveEntries,
 ) -> Result<()> {
     if !file_path.exists() {
         log::info!(
@@ -157,50 +560,182 @@ fn archive_if_exists(
     }
 
     let archived_file_name = root_in_zip.join(file_path.file_name().unwrap());
+    collect_entries(archived_file_name, file_path, entries)
+}
 
+fn collect_entries(
+    archived_path: PathBuf,
+    file_path: &Path,
+    entries: &mut ArchiveEntries,
+) -> Result<()> {
     if file_path.is_dir() {
+        let dir = fs::read_dir(file_path)
+            .context(format!("failed to read directory: {}", file_path.display()))?;
+        for entry in dir {
+            let entry = entry?;
+            collect_entries(
+                archived_path.join(entry.file_name()),
+                &entry.path(),
+                entries,
+            )?;
+        }
+    }
+
+    entries.insert(
+        archived_path,
+        EntrySource::Path(file_path.to_path_buf()).into(),
+    );
+
+    Ok(())
+}
+
+// Timestamp of all archive entries, can be overridden
+// with SOURCE_DATE_EPOCH for reproducible builds
+fn source_date_epoch() -> Result<u64> {
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .trim()
+            .parse()
+            .context(format!("invalid SOURCE_DATE_EPOCH value: {epoch}")),
+        Err(_) => Ok(0),
+    }
+}
+
+fn write_tar<W: Write>(writer: W, entries: &ArchiveEntries, mtime: u64) -> Result<W> {
+    let mut tarball = TarBuilder::new(writer);
+    for (archived_path, entry) in entries {
+        append_entry(&mut tarball, archived_path, entry, mtime)?;
+    }
+
+    Ok(tarball.into_inner()?)
+}
+
+fn write_zip(file: File, entries: &ArchiveEntries, mtime: u64, level: i32) -> Result<()> {
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .compression_level(Some(i64::from(level)))
+        .last_modified_time(zip_date_time(mtime));
+
+    for (archived_path, entry) in entries {
+        let name = archived_path.to_string_lossy();
+        let (is_dir, mode) = entry.kind()?;
+        let source = &entry.source;
+        if is_dir {
+            zip.add_directory(name, options.unix_permissions(mode))?;
+            continue;
+        }
+
+        zip.start_file(name, options.unix_permissions(mode))
+            .context(format!(
+                "failed to append file: {} to archive",
+                source.description()
+            ))?;
+        let (mut reader, _) = source.open()?;
+        io::copy(&mut reader, &mut zip)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+// Zip stores local date and time starting from 1980
+fn zip_date_time(epoch: u64) -> DateTime {
+    let days = i64::try_from(epoch / 86400).unwrap_or(i64::MAX);
+    let secs = epoch % 86400;
+
+    // Civil date from days since 1970-01-01, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date_time = (|| {
+        DateTime::from_date_and_time(
+            u16::try_from(year).ok()?,
+            u8::try_from(month).ok()?,
+            u8::try_from(day).ok()?,
+            u8::try_from(secs / 3600).ok()?,
+            u8::try_from(secs % 3600 / 60).ok()?,
+            u8::try_from(secs % 60).ok()?,
+        )
+        .ok()
+    })();
+
+    date_time.unwrap_or_default()
+}
+
+// Append the entry with normalized metadata, so that archive
+// depends only on the contents of packed files
+fn append_entry<W: Write>(
+    tarball: &mut TarBuilder<W>,
+    archived_path: &Path,
+    entry: &ArchiveEntry,
+    mtime: u64,
+) -> Result<()> {
+    let (is_dir, mode) = entry.kind()?;
+    let source = &entry.source;
+
+    let mut header = Header::new_gnu();
+    header.set_mtime(mtime);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mode(mode);
+
+    if is_dir {
+        header.set_entry_type(EntryType::Directory);
+        header.set_size(0);
         tarball
-            .append_dir_all(archived_file_name, file_path)
+            .append_data(&mut header, archived_path, io::empty())
             .context(format!(
                 "failed to append directory: {} to archive",
-                file_path.display()
+                source.description()
             ))?;
     } else {
-        let mut opened_file = File::open(file_path)
-            .context(format!("failed to open file {}", &file_path.display()))?;
-
+        let (reader, len) = source.open()?;
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(len);
         tarball
-            .append_file(archived_file_name, &mut opened_file)
+            .append_data(&mut header, archived_path, reader)
             .context(format!(
                 "failed to append file: {} to archive",
-                file_path.display()
+                source.description()
             ))?;
     }
 
     Ok(())
 }
 
-fn get_latest_plugin_version(plugin_dir: &Path) -> Result<String> {
-    let cargo_toml =
-        fs::read_to_string(plugin_dir.join("Cargo.toml")).expect("Failed to read Cargo.toml");
-
-    let parsed: toml::Value = toml::de::from_str(&cargo_toml).expect("Failed to parse TOML");
-
-    if let Some(package) = parsed.get("package") {
-        if let Some(version) = package.get("version") {
-            return Ok(version
-                .to_string()
-                .strip_prefix("\"")
-                .unwrap()
-                .strip_suffix("\"")
-                .unwrap()
-                .to_string());
-        }
-        bail!("Couldn't find version in plugin Cargo.toml");
-    }
-
-    bail!(
-        "Couldn't resolve plugin version from Cargo.toml at {}",
-        plugin_dir.display()
+// Write checksum next to the archive in the `sha256sum` format
+fn write_checksum(archive_path: &Path) -> Result<PathBuf> {
+    let mut archive = File::open(archive_path)
+        .context(format!("failed to open archive {}", archive_path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut archive, &mut hasher).context("failed to compute archive checksum")?;
+
+    let mut checksum_path = archive_path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    let checksum_path = PathBuf::from(checksum_path);
+
+    let archive_name = archive_path
+        .file_name()
+        .expect("archive path always has a file name")
+        .to_string_lossy();
+    fs::write(
+        &checksum_path,
+        format!("{:x}  {archive_name}\n", hasher.finalize()),
     )
+    .context(format!(
+        "failed to write checksum to {}",
+        checksum_path.display()
+    ))?;
+
+    Ok(checksum_path)
 }
@@ -0,0 +1,364 @@
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use log::{error, info};
+use object::{Object, ObjectSymbol};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::commands::output::{coded, emit, ErrorCode, Event};
+use crate::commands::plugin::pack::{ArchiveFormat, BundleIndex, BUNDLE_INDEX_NAME, LIB_EXT};
+
+// Symbol exported by `#[service_registrar]` of picodata-plugin
+const SERVICE_REGISTRAR_SYMBOL: &str = "pico_service_registrar";
+
+const MANIFEST_NAME: &str = "manifest.yaml";
+
+#[derive(Deserialize)]
+struct ManifestService {
+    name: String,
+    #[serde(default)]
+    default_configuration: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(default)]
+    services: Vec<ManifestService>,
+    #[serde(default)]
+    migration: Vec<String>,
+}
+
+#[derive(Default)]
+struct ArchiveContents {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl ArchiveContents {
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.keys().chain(self.dirs.iter())
+    }
+}
+
+fn read_tar<R: Read>(reader: R) -> Result<ArchiveContents> {
+    let mut contents = ArchiveContents::default();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if entry.header().entry_type().is_dir() {
+            contents.dirs.insert(path);
+            continue;
+        }
+
+        let mut data = vec![];
+        entry
+            .read_to_end(&mut data)
+            .context(format!("failed to read {} from archive", path.display()))?;
+        contents.files.insert(path, data);
+    }
+
+    Ok(contents)
+}
+
+fn read_zip(file: File) -> Result<ArchiveContents> {
+    let mut contents = ArchiveContents::default();
+    let mut archive = zip::ZipArchive::new(BufReader::new(file))?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let path = PathBuf::from(entry.name());
+        if entry.is_dir() {
+            contents.dirs.insert(path);
+            continue;
+        }
+
+        let mut data = vec![];
+        entry
+            .read_to_end(&mut data)
+            .context(format!("failed to read {} from archive", path.display()))?;
+        contents.files.insert(path, data);
+    }
+
+    Ok(contents)
+}
+
+fn read_archive(archive_path: &Path) -> Result<ArchiveContents> {
+    let Some(format) = ArchiveFormat::from_path(archive_path) else {
+        bail!(
+            "unknown format of archive {}, expected .tar.gz, .tar.zst, .tar or .zip",
+            archive_path.display()
+        );
+    };
+
+    let file = File::open(archive_path)
+        .context(format!("failed to open archive {}", archive_path.display()))?;
+    let reader = BufReader::new(file);
+
+    let contents = match format {
+        ArchiveFormat::Tar => read_tar(reader),
+        ArchiveFormat::TarGz => read_tar(GzDecoder::new(reader)),
+        ArchiveFormat::TarZst => read_tar(zstd::Decoder::with_buffer(reader)?),
+        ArchiveFormat::Zip => read_zip(reader.into_inner()),
+    };
+
+    contents.context(format!("failed to read archive {}", archive_path.display()))
+}
+
+// Plugin root is `<name>/<version>/`, located by the manifest
+fn find_plugin_root(contents: &ArchiveContents, problems: &mut Vec<String>) -> Option<PathBuf> {
+    let roots: Vec<&Path> = contents
+        .files
+        .keys()
+        .filter(|path| path.components().count() == 3 && path.ends_with(MANIFEST_NAME))
+        .filter_map(|path| path.parent())
+        .collect();
+
+    match roots.as_slice() {
+        [root] => Some(root.to_path_buf()),
+        [] => {
+            problems.push(format!(
+                "{MANIFEST_NAME} not found at <name>/<version>/{MANIFEST_NAME}"
+            ));
+            None
+        }
+        _ => {
+            let roots: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+            problems.push(format!(
+                "archive must contain a single plugin, found: {}",
+                roots.join(", ")
+            ));
+            None
+        }
+    }
+}
+
+fn check_library(lib_path: &Path, data: &[u8], problems: &mut Vec<String>) {
+    let library = match object::File::parse(data) {
+        Ok(library) => library,
+        Err(e) => {
+            problems.push(format!(
+                "{} is not a valid shared library: {e}",
+                lib_path.display()
+            ));
+            return;
+        }
+    };
+
+    // Mach-O symbols are prefixed with an underscore
+    let exports_registrar = library.dynamic_symbols().any(|symbol| {
+        symbol.is_definition()
+            && symbol.name().is_ok_and(|name| {
+                name.strip_prefix('_').unwrap_or(name) == SERVICE_REGISTRAR_SYMBOL
+            })
+    });
+    if !exports_registrar {
+        problems.push(format!(
+            "{} does not export {SERVICE_REGISTRAR_SYMBOL}, is #[service_registrar] missing?",
+            lib_path.display()
+        ));
+    }
+}
+
+fn check_manifest(
+    root: &Path,
+    version: &str,
+    contents: &ArchiveContents,
+    problems: &mut Vec<String>,
+) -> Option<Manifest> {
+    let manifest_path = root.join(MANIFEST_NAME);
+    let manifest: Manifest = match serde_yaml::from_slice(&contents.files[&manifest_path]) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            problems.push(format!("failed to parse {}: {e}", manifest_path.display()));
+            return None;
+        }
+    };
+
+    if manifest.version != version {
+        problems.push(format!(
+            "manifest version {} does not match plugin directory {}",
+            manifest.version,
+            root.display()
+        ));
+    }
+
+    for migration in &manifest.migration {
+        if !contents.files.contains_key(&root.join(migration)) {
+            problems.push(format!(
+                "migration {migration} listed in the manifest is missing"
+            ));
+        }
+    }
+
+    for service in &manifest.services {
+        if service
+            .default_configuration
+            .as_ref()
+            .is_none_or(Value::is_null)
+        {
+            problems.push(format!(
+                "service {} has no default configuration",
+                service.name
+            ));
+        }
+    }
+
+    Some(manifest)
+}
+
+fn check_unexpected_entries(
+    roots: &[(PathBuf, Option<Manifest>)],
+    contents: &ArchiveContents,
+    problems: &mut Vec<String>,
+) {
+    for path in contents.paths() {
+        if path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            problems.push(format!("entry {} escapes the archive", path.display()));
+            continue;
+        }
+
+        // Bundle index and directories leading to the plugin roots are fine
+        if path == Path::new(BUNDLE_INDEX_NAME)
+            || roots.iter().any(|(root, _)| root.starts_with(path))
+        {
+            continue;
+        }
+
+        let Some((root, manifest)) = roots.iter().find(|(root, _)| path.starts_with(root)) else {
+            let roots: Vec<String> = roots.iter().map(|(r, _)| r.display().to_string()).collect();
+            problems.push(format!(
+                "unexpected entry {} outside of {}",
+                path.display(),
+                roots.join(", ")
+            ));
+            continue;
+        };
+        let migrations_dir = root.join("migrations");
+
+        // Picodata applies only migrations listed in the manifest
+        if let Some(manifest) = manifest {
+            let Ok(relative_path) = path.strip_prefix(root) else {
+                continue;
+            };
+            if path.starts_with(&migrations_dir)
+                && contents.files.contains_key(path)
+                && !manifest
+                    .migration
+                    .iter()
+                    .any(|m| Path::new(m) == relative_path)
+            {
+                problems.push(format!(
+                    "migration {} is not listed in the manifest",
+                    relative_path.display()
+                ));
+            }
+        }
+    }
+}
+
+// Bundle lists its plugins in the index, plain archive has a single plugin
+fn find_plugin_roots(contents: &ArchiveContents, problems: &mut Vec<String>) -> Vec<PathBuf> {
+    let Some(index) = contents.files.get(Path::new(BUNDLE_INDEX_NAME)) else {
+        return find_plugin_root(contents, problems).into_iter().collect();
+    };
+
+    let index: BundleIndex = match serde_json::from_slice(index) {
+        Ok(index) => index,
+        Err(e) => {
+            problems.push(format!("failed to parse {BUNDLE_INDEX_NAME}: {e}"));
+            return vec![];
+        }
+    };
+
+    let mut roots = vec![];
+    for plugin in index.plugins {
+        let root = Path::new(&plugin.name).join(&plugin.version);
+        if contents.files.contains_key(&root.join(MANIFEST_NAME)) {
+            roots.push(root);
+        } else {
+            problems.push(format!(
+                "plugin {} listed in {BUNDLE_INDEX_NAME} has no {MANIFEST_NAME}",
+                root.display()
+            ));
+        }
+    }
+
+    if roots.is_empty() && problems.is_empty() {
+        problems.push(format!("{BUNDLE_INDEX_NAME} lists no plugins"));
+    }
+
+    roots
+}
+
+/// Check the plugin archive or bundle and return every found problem.
+pub fn verify(archive_path: &Path) -> Result<Vec<String>> {
+    let contents = read_archive(archive_path)?;
+
+    let mut problems = vec![];
+    let mut roots = vec![];
+    for root in find_plugin_roots(&contents, &mut problems) {
+        let mut root_components = root.components();
+        let name = root_components
+            .next()
+            .expect("root has two components")
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+        let version = root_components
+            .next()
+            .expect("root has two components")
+            .as_os_str()
+            .to_string_lossy()
+            .to_string();
+
+        let lib_path = root.join(format!("lib{}.{LIB_EXT}", name.replace('-', "_")));
+        match contents.files.get(&lib_path) {
+            Some(data) => check_library(&lib_path, data, &mut problems),
+            None => problems.push(format!("shared library {} is missing", lib_path.display())),
+        }
+
+        let manifest = check_manifest(&root, &version, &contents, &mut problems);
+        roots.push((root, manifest));
+    }
+
+    if !roots.is_empty() {
+        check_unexpected_entries(&roots, &contents, &mut problems);
+    }
+
+    Ok(problems)
+}
+
+pub fn cmd(archive_path: &Path) -> Result<()> {
+    let problems = verify(archive_path)?;
+
+    emit(&Event::ArchiveVerified {
+        path: archive_path,
+        problems: &problems,
+    });
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            error!("{problem}");
+        }
+        return Err(coded(
+            ErrorCode::InvalidArchive,
+            format!(
+                "archive {} has {} problem(s)",
+                archive_path.display(),
+                problems.len()
+            ),
+        ));
+    }
+
+    info!("archive {} is valid", archive_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use anyhow::{bail, Context, Result};
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+const MANIFEST_TEMPLATE_NAME: &str = "manifest.yaml.template";
+
+// `*` must not match across directories, `**` does
+pub const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawPackConfig {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    rename: BTreeMap<PathBuf, PathBuf>,
+    mode: BTreeMap<String, String>,
+}
+
+/// Packaging policy of the plugin from `[package.metadata.pike.pack]` of `Cargo.toml`.
+///
+/// All paths and globs except `include` are relative to the plugin directory
+/// in the archive `<name>/<version>/`. Included files keep their paths
+/// relative to the package directory there, so `bin/run.sh` is the same
+/// path for `include` and `exclude`. Included files are copied by the build
+/// script helper, see `helpers::build`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "RawPackConfig")]
+pub struct PackConfig {
+    /// Files to leave out, globs
+    exclude: Vec<Pattern>,
+    /// New paths of files or directories in the archive
+    rename: BTreeMap<PathBuf, PathBuf>,
+    /// File modes by glob, the most specific (longest) glob goes first
+    mode: Vec<(Pattern, u32)>,
+}
+
+fn is_relative_inside(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+impl TryFrom<RawPackConfig> for PackConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawPackConfig) -> Result<Self> {
+        for (from, to) in &raw.rename {
+            if !is_relative_inside(from) || !is_relative_inside(to) {
+                bail!(
+                    "rename {} = {} must use relative paths inside the plugin directory",
+                    from.display(),
+                    to.display()
+                );
+            }
+        }
+
+        for glob in &raw.include {
+            Pattern::new(glob).context(format!("invalid include glob {glob}"))?;
+        }
+
+        let exclude = raw
+            .exclude
+            .iter()
+            .map(|glob| Pattern::new(glob).context(format!("invalid exclude glob {glob}")))
+            .collect::<Result<_>>()?;
+
+        let mut mode = raw
+            .mode
+            .iter()
+            .map(|(glob, mode)| {
+                let pattern = Pattern::new(glob).context(format!("invalid mode glob {glob}"))?;
+                let mode = u32::from_str_radix(mode, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o7777)
+                    .context(format!(
+                        "invalid mode {mode} of {glob}, expected octal like 644"
+                    ))?;
+                Ok((pattern, mode))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        mode.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.as_str().len()));
+
+        Ok(Self {
+            exclude,
+            rename: raw.rename,
+            mode,
+        })
+    }
+}
+
+impl PackConfig {
+    fn from_metadata(metadata: &serde_json::Value) -> Result<Self> {
+        match metadata.pointer("/pike/pack") {
+            Some(pack) => Self::deserialize(pack).context("invalid [package.metadata.pike.pack]"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Path is excluded itself or is inside of excluded directory.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| {
+                self.exclude
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(ancestor, MATCH_OPTIONS))
+            })
+    }
+
+    /// Path of the file in the archive after renames.
+    pub fn renamed(&self, path: &Path) -> PathBuf {
+        for (from, to) in &self.rename {
+            if let Ok(rest) = path.strip_prefix(from) {
+                if rest.as_os_str().is_empty() {
+                    return to.clone();
+                }
+                return to.join(rest);
+            }
+        }
+
+        path.to_path_buf()
+    }
+
+    /// Mode of the file set by the config.
+    pub fn mode(&self, path: &Path) -> Option<u32> {
+        self.mode
+            .iter()
+            .find(|(pattern, _)| pattern.matches_path_with(path, MATCH_OPTIONS))
+            .map(|(_, mode)| *mode)
+    }
+}
+
+#[derive(Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+    manifest_path: PathBuf,
+    #[serde(default)]
+    metadata: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    workspace_members: Vec<String>,
+    workspace_root: PathBuf,
+    target_directory: PathBuf,
+}
+
+/// Package of the project as resolved by cargo.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    /// Version with workspace inheritance applied
+    pub version: String,
+    /// Directory of the package's `Cargo.toml`
+    pub dir: PathBuf,
+    pub pack: PackConfig,
+}
+
+impl Package {
+    /// Plugins have the manifest template next to `Cargo.toml`.
+    pub fn is_plugin(&self) -> bool {
+        self.dir.join(MANIFEST_TEMPLATE_NAME).exists()
+    }
+
+    /// File name of the plugin library copied by the build script, e.g. `libmy_plugin.so`.
+    pub fn lib_file_name(&self, ext: &str) -> String {
+        format!("lib{}.{ext}", self.name.replace('-', "_"))
+    }
+}
+
+/// Plugin project, single crate or workspace, described by `cargo metadata`.
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// Directory the project was loaded from
+    pub path: PathBuf,
+    pub workspace_root: PathBuf,
+    /// Target directory with `.cargo/config.toml` and `CARGO_TARGET_DIR` applied
+    pub target_dir: PathBuf,
+    /// Packages of the project: workspace members, or the single package
+    /// if the project was loaded from a member directory
+    pub packages: Vec<Package>,
+    pub is_workspace: bool,
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Self> {
+        let path = path
+            .canonicalize()
+            .context(format!("failed to resolve {}", path.display()))?;
+        let manifest_path = path.join("Cargo.toml");
+
+        let output = Command::new("cargo")
+            .args(["metadata", "--no-deps", "--format-version", "1"])
+            .arg("--manifest-path")
+            .arg(&manifest_path)
+            .current_dir(&path)
+            .output()
+            .context("failed to run cargo metadata")?;
+        if !output.status.success() {
+            bail!(
+                "cargo metadata {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let metadata: Metadata = serde_json::from_slice(&output.stdout)
+            .context("failed to parse output of cargo metadata")?;
+
+        let mut packages: Vec<Package> = metadata
+            .packages
+            .into_iter()
+            .filter(|package| metadata.workspace_members.contains(&package.id))
+            .map(|package| {
+                Ok(Package {
+                    dir: package
+                        .manifest_path
+                        .parent()
+                        .expect("manifest path always has a parent")
+                        .to_path_buf(),
+                    pack: PackConfig::from_metadata(&package.metadata)
+                        .context(format!("failed to load package {}", package.name))?,
+                    name: package.name,
+                    version: package.version,
+                })
+            })
+            .collect::<Result<_>>()?;
+        packages.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+        // Member directory is a project of its own
+        let mut is_workspace =
+            packages.len() > 1 || packages.iter().all(|p| p.dir != metadata.workspace_root);
+        if path != metadata.workspace_root {
+            if let Some(package) = packages.iter().find(|p| p.dir == path) {
+                packages = vec![package.clone()];
+                is_workspace = false;
+            }
+        }
+
+        Ok(Self {
+            path,
+            workspace_root: metadata.workspace_root,
+            target_dir: metadata.target_directory,
+            packages,
+            is_workspace,
+        })
+    }
+
+    /// Plugin packages of the project. Single crate is always a plugin,
+    /// in workspace only members with the manifest template are.
+    pub fn plugins(&self) -> impl Iterator<Item = &Package> {
+        let is_workspace = self.is_workspace;
+        self.packages
+            .iter()
+            .filter(move |package| !is_workspace || package.is_plugin())
+    }
+
+    pub fn package(&self, name: &str) -> Option<&Package> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Target directory given on the command line, relative to the project,
+    /// or the one configured for cargo.
+    pub fn target_dir(&self, target_dir: Option<&Path>) -> PathBuf {
+        target_dir.map_or_else(|| self.target_dir.clone(), |dir| self.path.join(dir))
+    }
+}
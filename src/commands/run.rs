@@ -1,27 +1,33 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use colored::Colorize;
 use derive_builder::Builder;
 use lib::cargo_build;
-use log::{error, info};
+use log::{error, info, warn};
 use nix::sys::signal::{kill, Signal};
-use nix::unistd::Pid;
+use nix::unistd::{fork, ForkResult, Pid};
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
 use std::os::unix::fs::symlink;
 use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::process::{self, Child, Command, Stdio};
 use std::str;
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 use std::{fs, path::PathBuf};
 
+use crate::commands::instance_log::{self, LogFilter, LogLevel, LogRotation, RotatingLogFile};
 use crate::commands::lib;
 use crate::commands::lib::check_running_instances;
+use crate::commands::lua::eval_on_socket;
+use crate::commands::migrations;
+use crate::commands::output::{self, coded, emit, ErrorCode, Event};
+use crate::commands::project::{Package, Project};
 
 const BAFFLED_WHALE: &str = r"
   __________________________________________________________
@@ -80,11 +86,19 @@ pub struct Topology {
     pub plugins: BTreeMap<String, Plugin>,
     #[serde(default)]
     pub enviroment: BTreeMap<String, String>,
+    #[serde(default)]
+    pub log_rotation: LogRotation,
 }
 
 impl Topology {
-    fn find_plugin_versions(&mut self, plugins_dir: &Path) -> Result<()> {
+    fn find_plugin_versions(&mut self, plugins_dir: &Path, project: &Project) -> Result<()> {
         for (plugin_name, plugin) in &mut self.plugins {
+            // Plugins of the project are built with their current version
+            if let Some(package) = project.package(plugin_name) {
+                plugin.version = Some(package.version.clone());
+                continue;
+            }
+
             let current_plugin_dir = plugins_dir.join(plugin_name);
 
             if !current_plugin_dir.exists() {
@@ -109,6 +123,30 @@ impl Topology {
         }
         Ok(())
     }
+
+    // Migrations of the project plugins must get a value for every context placeholder
+    fn check_migration_context(&self, project: &Project) -> Result<()> {
+        for (plugin_name, plugin) in &self.plugins {
+            let Some(package) = project.package(plugin_name) else {
+                continue;
+            };
+
+            let lint = migrations::check_context(&package.dir, &plugin.migration_context)?;
+            for problem in &lint.unused {
+                warn!("plugin {plugin_name}: {problem}");
+            }
+            if !lint.missing.is_empty() {
+                return Err(coded(
+                    ErrorCode::InvalidTopology,
+                    format!(
+                        "migration_context of plugin {plugin_name} in topology is incomplete: {}",
+                        lint.missing.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 fn enable_plugins(topology: &Topology, data_dir: &Path, picodata_path: &PathBuf) -> Result<()> {
@@ -203,16 +241,20 @@ fn enable_plugins(topology: &Topology, data_dir: &Path, picodata_path: &PathBuf)
     }
 
     for (plugin_name, plugin) in &topology.plugins {
-        info!(
-            "Plugin {plugin_name}:{} has been enabled",
-            plugin.version.as_ref().unwrap()
-        );
+        let plugin_version = plugin.version.as_ref().unwrap();
+        info!("Plugin {plugin_name}:{plugin_version} has been enabled");
+        emit(&Event::PluginEnabled {
+            plugin: plugin_name,
+            version: plugin_version,
+        });
     }
 
     Ok(())
 }
 
 const GET_VERSION_LUA: &str = "\\lua\npico.instance_info().name\n";
+const GET_STATE_LUA: &str = "pico.instance_info().current_state.variant";
+pub(crate) const SUPERVISOR_PID_FILE: &str = "supervisor.pid";
 
 fn get_instance_name(picodata_path: &Path, instance_data_dir: &Path) -> Result<String> {
     let admin_soket = instance_data_dir.join("admin.sock");
@@ -262,24 +304,18 @@ fn get_instance_name(picodata_path: &Path, instance_data_dir: &Path) -> Result<S
     bail!("get version error: {stdout}");
 }
 
-fn is_plugin_dir(path: &Path) -> bool {
-    if !path.is_dir() {
-        return false;
-    }
+// Project is built only if it has plugins
+fn load_plugin_project(path: &Path) -> Result<Option<Project>> {
     if !path.join("Cargo.toml").exists() {
-        return false;
+        return Ok(None);
     }
 
-    if path.join("manifest.yaml.template").exists() {
-        return true;
+    let project = Project::load(path)?;
+    if !project.packages.iter().any(Package::is_plugin) {
+        return Ok(None);
     }
 
-    fs::read_dir(path)
-        .unwrap()
-        .filter(Result::is_ok)
-        .map(|e| e.unwrap().path())
-        .filter(|e| e.is_dir())
-        .any(|dir| dir.join("manifest.yaml.template").exists())
+    Ok(Some(project))
 }
 
 #[allow(dead_code)]
@@ -302,6 +338,8 @@ pub struct PicodataInstance {
     child: Child,
     daemon: bool,
     disable_colors: bool,
+    log_filter: LogFilter,
+    log_rotation: LogRotation,
     data_dir: PathBuf,
     log_file_path: PathBuf,
     pg_port: u16,
@@ -432,6 +470,8 @@ impl PicodataInstance {
             child,
             daemon: run_params.daemon,
             disable_colors: run_params.disable_colors,
+            log_filter: run_params.log_filter.clone(),
+            log_rotation: run_params.topology.log_rotation.clone(),
             data_dir: instance_data_dir,
             log_file_path,
             pg_port,
@@ -456,8 +496,10 @@ impl PicodataInstance {
         let picodata_output = match picodata_output {
             Ok(o) => o,
             Err(err) if err.kind() == ErrorKind::NotFound => {
-                println!("{BAFFLED_WHALE}");
-                bail!("Picodata not found")
+                if !output::is_json() {
+                    println!("{BAFFLED_WHALE}");
+                }
+                return Err(coded(ErrorCode::PicodataNotFound, "Picodata not found"));
             }
             Err(err) => bail!("failed to get picodata version ({err})"),
         };
@@ -510,16 +552,17 @@ impl PicodataInstance {
             rnd.random_range(30..220),
         );
 
-        let file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&self.log_file_path)
+        let file = RotatingLogFile::create(&self.log_file_path, self.log_rotation.clone())
             .expect("Failed to open log file");
         let file = Arc::new(Mutex::new(file));
 
         let mut log_threads = vec![];
 
+        let data_dir_name = format!("i{}", self.instance_id);
+        let instance_visible = self
+            .log_filter
+            .is_instance_visible(&[&self.instance_name, &data_dir_name]);
+
         let stdout = self.child.stdout.take().expect("Failed to capture stdout");
         let stderr = self.child.stderr.take().expect("Failed to capture stderr");
         let outputs: [Box<dyn Read + Send>; 2] = [Box::new(stdout), Box::new(stderr)];
@@ -529,14 +572,35 @@ impl PicodataInstance {
                 log_prefix = log_prefix.custom_color(instance_name_color).to_string();
             }
             let file = file.clone();
+            let log_filter = self.log_filter.clone();
+            let disable_colors = self.disable_colors;
+            // Keep stdout clean for JSON events
+            let to_stderr = output::is_json();
 
             let wrapper = move || {
                 let stdout_lines = BufReader::new(child_output).lines();
                 for line in stdout_lines {
                     let line = line.unwrap();
-                    println!("{log_prefix}{line}");
-                    writeln!(file.lock().unwrap(), "{line}")
+                    file.lock()
+                        .unwrap()
+                        .write_line(&line)
                         .expect("Failed to write line to log file");
+
+                    let level = LogLevel::parse(&line);
+                    if !instance_visible || !log_filter.is_line_visible(&line, level) {
+                        continue;
+                    }
+
+                    let line = if disable_colors {
+                        line
+                    } else {
+                        instance_log::highlight(&line, level).to_string()
+                    };
+                    if to_stderr {
+                        eprintln!("{log_prefix}{line}");
+                    } else {
+                        println!("{log_prefix}{line}");
+                    }
                 }
             };
 
@@ -655,8 +719,9 @@ pub struct Params {
     base_pg_port: u16,
     #[builder(default = "false")]
     use_release: bool,
-    #[builder(default = "PathBuf::from(\"target\")")]
-    target_dir: PathBuf,
+    /// Target directory configured for cargo if not set
+    #[builder(default, setter(into))]
+    target_dir: Option<PathBuf>,
     #[builder(default = "false")]
     daemon: bool,
     #[builder(default = "false")]
@@ -667,33 +732,53 @@ pub struct Params {
     no_build: bool,
     #[builder(default = "PathBuf::from(\"./picodata.yaml\")")]
     config_path: PathBuf,
+    #[builder(default)]
+    log_filter: LogFilter,
+    #[builder(default)]
+    cargo_options: lib::CargoOptions,
+    /// How long to wait for all instances to become Online
+    #[builder(default = "Duration::from_secs(120)")]
+    ready_timeout: Duration,
 }
 
 pub fn cluster(params: &Params) -> Result<Vec<PicodataInstance>> {
     let cur_running_instance = check_running_instances(&params.data_dir, &params.plugin_path)?;
     if let Some(sock_path) = cur_running_instance {
-        bail!("cluster has already started, can connect via {sock_path}");
+        return Err(coded(
+            ErrorCode::ClusterAlreadyRunning,
+            format!("cluster has already started, can connect via {sock_path}"),
+        ));
     }
 
     let mut params = params.clone();
     params.data_dir = params.plugin_path.join(&params.data_dir);
 
     let mut plugins_dir = None;
-    if is_plugin_dir(&params.plugin_path) {
+    if let Some(project) = load_plugin_project(&params.plugin_path)? {
         let build_type = if params.use_release {
-            plugins_dir = Some(params.plugin_path.join(params.target_dir.join("release")));
             lib::BuildType::Release
         } else {
-            plugins_dir = Some(params.plugin_path.join(params.target_dir.join("debug")));
             lib::BuildType::Debug
         };
+        let target_dir = project.target_dir(params.target_dir.as_deref());
+        plugins_dir = Some(lib::artifact_dir(
+            &target_dir,
+            &build_type,
+            &params.cargo_options,
+        ));
         if !params.no_build {
-            cargo_build(build_type, &params.target_dir, &params.plugin_path)?;
+            cargo_build(
+                build_type,
+                &target_dir,
+                &params.plugin_path,
+                &params.cargo_options,
+            )?;
         };
 
         params
             .topology
-            .find_plugin_versions(plugins_dir.as_ref().unwrap())?;
+            .find_plugin_versions(plugins_dir.as_ref().unwrap(), &project)?;
+        params.topology.check_migration_context(&project)?;
     }
 
     info!("Running the cluster...");
@@ -701,6 +786,34 @@ pub fn cluster(params: &Params) -> Result<Vec<PicodataInstance>> {
 
     let mut picodata_processes = vec![];
 
+    // Do not leave half-started cluster behind
+    if let Err(e) = bootstrap(&params, plugins_dir.as_deref(), &mut picodata_processes) {
+        for process in &mut picodata_processes {
+            process.kill().unwrap_or_else(|e| {
+                error!("failed to kill picodata instances: {e:#}");
+            });
+        }
+        return Err(e);
+    }
+
+    let total_instances = picodata_processes.len();
+    let launch_time_sec = start_cluster_run.elapsed().as_secs();
+    info!(
+        "Picodata cluster has started (launch time: {launch_time_sec} sec, total instances: {total_instances})"
+    );
+    emit(&Event::ClusterStarted {
+        total_instances: u16::try_from(total_instances).expect("instance count fits u16"),
+        launch_time_sec,
+    });
+
+    Ok(picodata_processes)
+}
+
+fn bootstrap(
+    params: &Params,
+    plugins_dir: Option<&Path>,
+    picodata_processes: &mut Vec<PicodataInstance>,
+) -> Result<()> {
     let tiers_config = get_merged_cluster_tier_config(
         &params.plugin_path,
         &params.config_path,
@@ -718,58 +831,110 @@ pub fn cluster(params: &Params) -> Result<Vec<PicodataInstance>> {
                 params.base_http_port + instance_id,
                 params.base_pg_port + instance_id,
                 first_instance_bin_port,
-                plugins_dir.as_deref(),
+                plugins_dir,
                 tier_name,
-                &params,
+                params,
                 &params.topology.enviroment,
                 &tiers_config,
                 &params.picodata_path,
                 &params.config_path,
             )?;
 
-            picodata_processes.push(pico_instance);
-
             info!("i{instance_id} - started");
+            let properties = pico_instance.properties();
+            emit(&Event::InstanceStarted {
+                instance_name: properties.instance_name,
+                instance_id,
+                tier: properties.tier,
+                bin_port: *properties.bin_port,
+                http_port: *properties.http_port,
+                pg_port: *properties.pg_port,
+                data_dir: properties.data_dir,
+            });
+
+            picodata_processes.push(pico_instance);
         }
     }
 
-    // TODO: check cluster is started by logs or iproto
-    thread::sleep(Duration::from_secs(3));
+    wait_cluster_ready(
+        picodata_processes,
+        &params.picodata_path,
+        params.ready_timeout,
+    )?;
 
     if !params.disable_plugin_install {
         info!("Enabling plugins...");
 
         if plugins_dir.is_some() {
-            let result = enable_plugins(&params.topology, &params.data_dir, &params.picodata_path);
-            if let Err(e) = result {
-                for process in &mut picodata_processes {
-                    process.kill().unwrap_or_else(|e| {
-                        error!("failed to kill picodata instances: {e:#}");
-                    });
+            enable_plugins(&params.topology, &params.data_dir, &params.picodata_path).map_err(
+                |e| {
+                    coded(
+                        ErrorCode::PluginInstallFailed,
+                        format!("failed to enable plugins: {e}"),
+                    )
+                },
+            )?;
+        }
+    };
+
+    Ok(())
+}
+
+// Poll every instance until all of them become Online
+fn wait_cluster_ready(
+    instances: &[PicodataInstance],
+    picodata_path: &Path,
+    timeout: Duration,
+) -> Result<()> {
+    let start = Instant::now();
+    let mut pending: Vec<&PicodataInstance> = instances.iter().collect();
+
+    loop {
+        pending.retain(|instance| {
+            let state = eval_on_socket(
+                picodata_path,
+                &instance.data_dir.join("admin.sock"),
+                GET_STATE_LUA,
+            );
+            match state {
+                Ok(state) => state.as_str() != Some("Online"),
+                Err(e) => {
+                    log::debug!("{e}");
+                    true
                 }
-                bail!("failed to enable plugins: {}", e.to_string());
             }
+        });
+
+        if pending.is_empty() {
+            return Ok(());
         }
-    };
 
-    info!(
-        "Picodata cluster has started (launch time: {} sec, total instances: {instance_id})",
-        start_cluster_run.elapsed().as_secs()
-    );
+        if start.elapsed() > timeout {
+            let names: Vec<&str> = pending.iter().map(|i| i.instance_name.as_str()).collect();
+            return Err(coded(
+                ErrorCode::ClusterNotReady,
+                format!(
+                    "instances {} are not online after {} sec",
+                    names.join(", "),
+                    timeout.as_secs()
+                ),
+            ));
+        }
 
-    Ok(picodata_processes)
+        thread::sleep(Duration::from_millis(500));
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::fn_params_excessive_bools)]
 #[allow(clippy::cast_possible_wrap)]
 pub fn cmd(params: &Params) -> Result<()> {
-    let mut pico_instances = cluster(params)?;
-
     if params.daemon {
-        return Ok(());
+        return daemonize(params);
     }
 
+    let mut pico_instances = cluster(params)?;
+
     // Set Ctrl+C handler. Upon recieving Ctrl+C signal
     // All instances would be killed, then joined and
     // destructors will be called
@@ -790,3 +955,167 @@ pub fn cmd(params: &Params) -> Result<()> {
 
     Ok(())
 }
+
+/// Status of the cluster bootstrap, reported by the supervisor to `run -d`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum DaemonStatus {
+    Ready,
+    Failed { code: ErrorCode, message: String },
+}
+
+fn supervisor_pid_file(params: &Params) -> PathBuf {
+    params
+        .plugin_path
+        .join(&params.data_dir)
+        .join(SUPERVISOR_PID_FILE)
+}
+
+// Fork the supervisor, which owns the cluster in the new session, and wait
+// until it reports that the cluster is ready
+fn daemonize(params: &Params) -> Result<()> {
+    let mut fds = [0; 2];
+    // Pipe must not leak into picodata instances, otherwise
+    // the parent would not notice the death of supervisor
+    // SAFETY: `fds` has room for the two descriptors written by `pipe2`
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        bail!(
+            "failed to create supervisor pipe: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    // SAFETY: both descriptors were just opened by `pipe2` and nothing else owns them
+    let (read_fd, write_fd) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    // SAFETY: pike has not spawned any threads yet, so the child
+    // does not inherit locks held by threads which do not exist in it
+    match unsafe { fork() }.context("failed to start cluster supervisor")? {
+        ForkResult::Parent { .. } => {
+            drop(write_fd);
+            let mut status = String::new();
+            BufReader::new(read_fd)
+                .read_line(&mut status)
+                .context("failed to read status of cluster supervisor")?;
+
+            match serde_json::from_str(&status) {
+                Ok(DaemonStatus::Ready) => Ok(()),
+                Ok(DaemonStatus::Failed { code, message }) => Err(coded(code, message)),
+                Err(_) => bail!("cluster supervisor exited unexpectedly"),
+            }
+        }
+        ForkResult::Child => {
+            drop(read_fd);
+            let code = supervise(params, write_fd);
+            process::exit(code);
+        }
+    }
+}
+
+fn report_daemon_status(status_pipe: &mut File, status: &DaemonStatus) {
+    let status = serde_json::to_string(status).expect("status is serializable");
+    let _ = writeln!(status_pipe, "{status}");
+}
+
+// Point stdin, stdout and stderr of the process to /dev/null
+fn detach_stdio() -> std::io::Result<()> {
+    let dev_null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: `dev_null` is open for the whole call and `dup2`
+        // atomically replaces the standard descriptor
+        if unsafe { libc::dup2(dev_null.as_raw_fd(), fd) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+fn supervise(params: &Params, mut status_pipe: File) -> i32 {
+    let pid_file = supervisor_pid_file(params);
+    let report_failure = |status_pipe: &mut File, e: &anyhow::Error| {
+        let _ = fs::remove_file(&pid_file);
+        report_daemon_status(
+            status_pipe,
+            &DaemonStatus::Failed {
+                code: output::error_code(e),
+                message: format!("{e:#}"),
+            },
+        );
+    };
+
+    // SAFETY: `setsid` has no preconditions, it fails only for a group leader
+    if unsafe { libc::setsid() } == -1 {
+        let e = anyhow!(
+            "failed to create session of cluster supervisor: {}",
+            std::io::Error::last_os_error()
+        );
+        report_failure(&mut status_pipe, &e);
+        return 1;
+    }
+
+    let started = fs::create_dir_all(pid_file.parent().expect("unreachable"))
+        .and_then(|()| fs::write(&pid_file, format!("{}\n", process::id())))
+        .context("failed to create supervisor pid file")
+        .and_then(|()| cluster(params));
+
+    let mut pico_instances = match started {
+        Ok(instances) => instances,
+        Err(e) => {
+            report_failure(&mut status_pipe, &e);
+            return 1;
+        }
+    };
+
+    let supervisor_pid = process::id();
+    print_daemon_summary(&pico_instances, supervisor_pid, &pid_file);
+    emit(&Event::DaemonStarted {
+        supervisor_pid,
+        pid_file: &pid_file,
+    });
+    let _ = std::io::stdout().flush();
+
+    // Detach from the terminal of `run -d`, otherwise readers of its
+    // output would wait for the whole cluster to stop
+    if let Err(e) = detach_stdio() {
+        for instance in &mut pico_instances {
+            let _ = instance.kill();
+        }
+        let e = anyhow!("failed to detach cluster supervisor from terminal: {e}");
+        report_failure(&mut status_pipe, &e);
+        return 1;
+    }
+    report_daemon_status(&mut status_pipe, &DaemonStatus::Ready);
+    drop(status_pipe);
+
+    for instance in &mut pico_instances {
+        let _ = instance.child.wait();
+    }
+    let _ = fs::remove_file(&pid_file);
+
+    0
+}
+
+fn print_daemon_summary(instances: &[PicodataInstance], supervisor_pid: u32, pid_file: &Path) {
+    if output::is_json() {
+        return;
+    }
+
+    println!("Cluster is running in the background:");
+    for instance in instances {
+        println!(
+            "  {} ({}): bin {}, http {}, pg {}",
+            instance.instance_name,
+            instance.tier,
+            instance.bin_port,
+            instance.http_port,
+            instance.pg_port
+        );
+    }
+    println!(
+        "Supervisor PID: {supervisor_pid} ({}), stop the cluster with `cargo pike stop`",
+        pid_file.display()
+    );
+}
@@ -2,12 +2,16 @@ use anyhow::{bail, Context, Result};
 use colored::Colorize;
 use derive_builder::Builder;
 use log::info;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
 use std::fs::{self};
 use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::commands::lib::get_active_socket_path;
+use crate::commands::output::{coded, emit, ErrorCode, Event};
+use crate::commands::run::SUPERVISOR_PID_FILE;
 
 #[derive(Debug, Builder)]
 pub struct Params {
@@ -19,10 +23,15 @@ pub struct Params {
 
 pub fn cmd(params: &Params) -> Result<()> {
     let instances_path = params.plugin_path.join(params.data_dir.join("cluster"));
-    let dirs = fs::read_dir(&instances_path).context(format!(
-        "cluster data dir with path {} does not exist",
-        instances_path.to_string_lossy()
-    ))?;
+    let dirs = fs::read_dir(&instances_path).map_err(|e| {
+        coded(
+            ErrorCode::ClusterNotFound,
+            format!(
+                "cluster data dir with path {} does not exist: {e}",
+                instances_path.to_string_lossy()
+            ),
+        )
+    })?;
 
     info!(
         "stopping picodata cluster, data folder: {}",
@@ -70,6 +79,11 @@ pub fn cmd(params: &Params) -> Result<()> {
                 link_name.to_string_lossy(),
                 "SKIPPED".yellow()
             );
+            emit(&Event::InstanceStopped {
+                instance_name: &link_name.to_string_lossy(),
+                pid,
+                skipped: true,
+            });
             continue;
         }
 
@@ -81,8 +95,38 @@ pub fn cmd(params: &Params) -> Result<()> {
             link_name.to_string_lossy(),
             "OK".green()
         );
+        emit(&Event::InstanceStopped {
+            instance_name: &link_name.to_string_lossy(),
+            pid,
+            skipped: false,
+        });
     }
 
+    stop_supervisor(params)?;
+
+    Ok(())
+}
+
+// Supervisor of the daemonized cluster exits by itself once all
+// instances are stopped, kill it anyway to not leave it hanging
+fn stop_supervisor(params: &Params) -> Result<()> {
+    let pid_file_path = params
+        .plugin_path
+        .join(&params.data_dir)
+        .join(SUPERVISOR_PID_FILE);
+    if !pid_file_path.exists() {
+        return Ok(());
+    }
+
+    let pid = read_pid_from_file(&pid_file_path).context("failed to read the PID file")?;
+    let pid = i32::try_from(pid).context("supervisor PID is too big")?;
+    if kill(Pid::from_raw(pid), None).is_ok() {
+        info!("stopping cluster supervisor: {pid}");
+        let _ = kill(Pid::from_raw(pid), Signal::SIGKILL);
+    }
+
+    fs::remove_file(&pid_file_path).context("failed to remove supervisor PID file")?;
+
     Ok(())
 }
 
@@ -0,0 +1,308 @@
+use anyhow::{bail, Context, Result};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use super::{rerun_if_glob_changed, GLOB_CHARS};
+use crate::commands::project::{PackConfig, MATCH_OPTIONS};
+
+/// Files copied into the `assets` folder of the plugin.
+#[derive(Debug, Clone)]
+pub(super) struct CustomAsset {
+    /// Path or glob relative to the plugin directory
+    pub from: String,
+    /// Path inside of the `assets` folder, directory for matches of a glob.
+    /// File name of the source if not set
+    pub to: Option<PathBuf>,
+    /// Missing asset fails the build instead of warning
+    pub required: bool,
+}
+
+/// Callback producing contents of the generated asset.
+pub type AssetGenerator = dyn Fn() -> Result<Vec<u8>, Box<dyn Error>>;
+
+/// Asset written into the `assets` folder by a callback at build time.
+#[derive(Clone)]
+pub(super) struct GeneratedAsset {
+    /// Path inside of the `assets` folder
+    pub to: PathBuf,
+    /// Files the contents are generated from, the build reruns when they change
+    pub inputs: Vec<PathBuf>,
+    pub generate: Rc<AssetGenerator>,
+}
+
+impl fmt::Debug for GeneratedAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedAsset")
+            .field("to", &self.to)
+            .field("inputs", &self.inputs)
+            .finish_non_exhaustive()
+    }
+}
+
+// Check if path is out of bounds of the `assets` folder
+fn is_inside_assets(path: &Path) -> bool {
+    !path
+        .components()
+        .any(|comp| matches!(comp, Component::ParentDir | Component::RootDir))
+}
+
+fn report_missing(asset: &CustomAsset, message: &str) {
+    if asset.required {
+        println!("cargo::error={message}");
+    } else {
+        println!("cargo::warning={message} - skipping");
+    }
+}
+
+fn copy_asset(from_asset_path: &Path, destination: &Path) -> Result<()> {
+    println!("cargo::rerun-if-changed={}", from_asset_path.display());
+
+    if from_asset_path.is_dir() {
+        if !destination.exists() {
+            fs::create_dir_all(destination)
+                .context(format!("failed to create {}", destination.display()))?;
+        }
+
+        let mut options = fs_extra::dir::CopyOptions::new();
+        options.overwrite = true;
+        options.copy_inside = true;
+        options.content_only = true;
+        fs_extra::dir::copy(from_asset_path, destination, &options).context(format!(
+            "failed to copy custom asset {}",
+            from_asset_path.display()
+        ))?;
+    } else {
+        // Create a directory into which a file should be copied
+        if let Some(parent_destination_directory) = destination.parent() {
+            fs::create_dir_all(parent_destination_directory).context(format!(
+                "failed to create {}",
+                parent_destination_directory.display()
+            ))?;
+        }
+        fs::copy(from_asset_path, destination).context(format!(
+            "failed to copy custom asset {}",
+            from_asset_path.display()
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn add_glob_asset(asset: &CustomAsset, assets_path: &Path) -> Result<()> {
+    rerun_if_glob_changed(Path::new(""), &asset.from);
+
+    let paths = match glob::glob_with(&asset.from, MATCH_OPTIONS) {
+        Ok(paths) => paths,
+        Err(e) => {
+            bail!("invalid custom asset glob {}: {e}", asset.from);
+        }
+    };
+    let mut paths: Vec<PathBuf> = paths.filter_map(Result::ok).collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        report_missing(
+            asset,
+            &format!("Custom asset glob {} does not match any file", asset.from),
+        );
+    }
+
+    let destination_dir = assets_path.join(asset.to.as_deref().unwrap_or(Path::new("")));
+    for path in paths {
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        copy_asset(&path, &destination_dir.join(file_name))?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn add_custom_assets(custom_assets: &[CustomAsset], plugin_path: &Path) -> Result<()> {
+    let assets_path = plugin_path.join("assets");
+
+    for asset in custom_assets {
+        if let Some(to_asset_path) = &asset.to {
+            if !is_inside_assets(to_asset_path) {
+                println!(
+                    "cargo::warning=Path to a custom asset destination {} goes out of the assets folder - skipping",
+                    to_asset_path.display()
+                );
+
+                continue;
+            }
+        }
+
+        if asset.from.contains(GLOB_CHARS) {
+            add_glob_asset(asset, &assets_path)?;
+            continue;
+        }
+
+        let from_asset_path = Path::new(&asset.from);
+        if !from_asset_path.exists() {
+            report_missing(
+                asset,
+                &format!("Couldn't find custom asset {}", from_asset_path.display()),
+            );
+
+            continue;
+        }
+
+        let to_asset_path = asset
+            .to
+            .as_deref()
+            .or_else(|| from_asset_path.file_name().map(Path::new))
+            .context(format!(
+                "custom asset {} has no file name, set its target",
+                asset.from
+            ))?;
+        copy_asset(from_asset_path, &assets_path.join(to_asset_path))?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn add_generated_assets(
+    generated_assets: &[GeneratedAsset],
+    plugin_path: &Path,
+) -> Result<()> {
+    for asset in generated_assets {
+        if !is_inside_assets(&asset.to) || asset.to.file_name().is_none() {
+            println!(
+                "cargo::error=Path to a generated asset {} must be a file inside of the assets folder",
+                asset.to.display()
+            );
+
+            continue;
+        }
+
+        for input in &asset.inputs {
+            println!("cargo::rerun-if-changed={}", input.display());
+        }
+
+        let contents = match (asset.generate)() {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!(
+                    "cargo::error=Failed to generate asset {}: {e}",
+                    asset.to.display()
+                );
+
+                continue;
+            }
+        };
+
+        let destination = plugin_path.join("assets").join(&asset.to);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).context(format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(&destination, contents).context(format!(
+            "failed to write generated asset {}",
+            asset.to.display()
+        ))?;
+    }
+
+    Ok(())
+}
+
+// Include globs and packaging policy from `[package.metadata.pike.pack]` of `Cargo.toml`
+fn load_pack_config(manifest_path: &Path) -> Result<(Vec<String>, PackConfig)> {
+    let manifest: toml::Value = toml::from_str(
+        &fs::read_to_string(manifest_path)
+            .context(format!("failed to read {}", manifest_path.display()))?,
+    )
+    .context(format!("failed to parse {}", manifest_path.display()))?;
+
+    let Some(pack) = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("pike"))
+        .and_then(|pike| pike.get("pack"))
+    else {
+        return Ok((vec![], PackConfig::default()));
+    };
+
+    let pack_config = pack
+        .clone()
+        .try_into()
+        .context("invalid [package.metadata.pike.pack]")?;
+    let include = match pack.get("include") {
+        Some(include) => include
+            .clone()
+            .try_into()
+            .context("invalid [package.metadata.pike.pack] include")?,
+        None => vec![],
+    };
+
+    Ok((include, pack_config))
+}
+
+fn collect_files(base_dir: &Path, relative_path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let path = base_dir.join(relative_path);
+    if !path.is_dir() {
+        files.push(relative_path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(&path).context(format!("failed to read {}", path.display()))? {
+        collect_files(base_dir, &relative_path.join(entry?.file_name()), files)?;
+    }
+
+    Ok(())
+}
+
+// Files matched by the include glob, relative to the package directory.
+// Directories are included with their contents
+fn included_files(package_dir: &Path, glob: &str) -> Result<Vec<PathBuf>> {
+    let pattern = Path::new(&glob::Pattern::escape(&package_dir.to_string_lossy())).join(glob);
+    let paths = glob::glob_with(&pattern.to_string_lossy(), MATCH_OPTIONS)
+        .context(format!("invalid include glob {glob}"))?;
+
+    let mut files = vec![];
+    for path in paths {
+        let path = path?;
+        let relative_path = path
+            .strip_prefix(package_dir)
+            .ok()
+            .filter(|path| is_inside_assets(path))
+            .context(format!(
+                "include glob {glob} matches files outside of the package"
+            ))?;
+        collect_files(package_dir, relative_path, &mut files)?;
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+// Copy files listed in `[package.metadata.pike.pack] include` into the assets folder.
+// Assets are packed into the root of the plugin directory in the archive, so path
+// of the file relative to the package is also its path for `exclude`
+pub(super) fn add_included_files(crate_dir: &Path, plugin_path: &Path) -> Result<()> {
+    let (include, pack_config) = load_pack_config(&crate_dir.join("Cargo.toml"))?;
+
+    for glob in &include {
+        rerun_if_glob_changed(crate_dir, glob);
+
+        let mut files = included_files(crate_dir, glob)?;
+        if files.is_empty() {
+            println!("cargo::warning=Include glob {glob} does not match any file - skipping");
+        }
+        files.retain(|file| !pack_config.is_excluded(file));
+
+        for file in files {
+            let source = crate_dir.join(&file);
+            println!("cargo::rerun-if-changed={}", source.display());
+            let destination = plugin_path.join("assets").join(&file);
+            fs::create_dir_all(destination.parent().unwrap_or(plugin_path))
+                .context(format!("failed to create directory for {}", file.display()))?;
+            fs::copy(&source, destination)
+                .context(format!("failed to copy included file {}", file.display()))?;
+        }
+    }
+
+    Ok(())
+}
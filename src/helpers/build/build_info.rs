@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const BUILD_INFO_NAME: &str = "BUILD_INFO.json";
+
+#[derive(Debug, Serialize)]
+pub struct GitInfo {
+    pub commit: String,
+    /// Tracked files had uncommitted changes when the build script last ran.
+    ///
+    /// Best-effort: edits of files do not rerun the build script by themselves.
+    pub dirty: bool,
+}
+
+/// Provenance of the plugin build, packed into archive as `BUILD_INFO.json`.
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub git: Option<GitInfo>,
+    pub rustc_version: Option<String>,
+    pub cargo_version: Option<String>,
+    pub profile: String,
+    pub target: String,
+    pub features: Vec<String>,
+    pub pike_version: String,
+    pub picodata_plugin_version: Option<String>,
+    /// `SOURCE_DATE_EPOCH` if set, otherwise Unix time of the HEAD commit or 0,
+    /// so the same commit always gets the same timestamp
+    pub timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+fn command_output(program: &str, args: &[&str], dir: &Path) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn git_info(crate_dir: &Path) -> Option<GitInfo> {
+    let commit = command_output("git", &["rev-parse", "HEAD"], crate_dir)?;
+    let status = command_output(
+        "git",
+        &["status", "--porcelain", "--untracked-files=no"],
+        crate_dir,
+    )?;
+
+    // Rerun build script on commit or checkout, otherwise commit would be stale
+    if let Some(git_dir) = command_output("git", &["rev-parse", "--absolute-git-dir"], crate_dir) {
+        let git_dir = PathBuf::from(git_dir);
+        println!("cargo::rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(head_ref) = command_output("git", &["symbolic-ref", "-q", "HEAD"], crate_dir) {
+            let head_ref_path = git_dir.join(head_ref);
+            if head_ref_path.exists() {
+                println!("cargo::rerun-if-changed={}", head_ref_path.display());
+            }
+        }
+    }
+
+    // Dirty flag is not watched, `git status` may rewrite the index
+    // and watching every tracked file is too costly in large repositories
+    Some(GitInfo {
+        commit,
+        dirty: !status.is_empty(),
+    })
+}
+
+fn tool_version(env_var: &str, default: &str, crate_dir: &Path) -> Option<String> {
+    let program = env::var(env_var).unwrap_or_else(|_| default.to_string());
+    command_output(&program, &["--version"], crate_dir)
+}
+
+// Cargo.lock lives in the root of workspace, which may be above the crate
+fn picodata_plugin_version(crate_dir: &Path) -> Option<String> {
+    let lock_path = crate_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.exists())?;
+    println!("cargo::rerun-if-changed={}", lock_path.display());
+    let lock: CargoLock = toml::from_str(&fs::read_to_string(lock_path).ok()?).ok()?;
+
+    lock.package
+        .into_iter()
+        .find(|package| package.name == "picodata-plugin")
+        .map(|package| package.version)
+}
+
+// Cargo passes enabled features as `CARGO_FEATURE_<NAME>` variables
+fn enabled_features() -> Vec<String> {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(name, _)| {
+            name.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    features
+}
+
+// Wall clock is never used, otherwise archives of the same commit would differ
+fn build_timestamp(crate_dir: &Path) -> u64 {
+    println!("cargo::rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .or_else(|| {
+            command_output("git", &["log", "-1", "--format=%ct"], crate_dir)?
+                .parse()
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+impl BuildInfo {
+    /// Collect build info from the environment of the build script.
+    pub fn collect(crate_dir: &Path) -> Self {
+        Self {
+            git: git_info(crate_dir),
+            rustc_version: tool_version("RUSTC", "rustc", crate_dir),
+            cargo_version: tool_version("CARGO", "cargo", crate_dir),
+            profile: env::var("PROFILE").unwrap_or_default(),
+            target: env::var("TARGET").unwrap_or_default(),
+            features: enabled_features(),
+            pike_version: env!("CARGO_PKG_VERSION").to_string(),
+            picodata_plugin_version: picodata_plugin_version(crate_dir),
+            timestamp: build_timestamp(crate_dir),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Problem of the rendered manifest, positions are 1-based.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+// Position of the nested `key: value` line below the top level `parent` key,
+// `nth` skips earlier matches, e.g. for duplicated service names.
+fn find_nested_key(
+    manifest: &str,
+    parent: &str,
+    key: &str,
+    value: &str,
+    nth: usize,
+) -> (usize, usize) {
+    nested_key_position(manifest, parent, key, value, nth).unwrap_or((1, 1))
+}
+
+fn nested_key_position(
+    manifest: &str,
+    parent: &str,
+    key: &str,
+    value: &str,
+    nth: usize,
+) -> Option<(usize, usize)> {
+    let (parent_line, _) = find_top_level_key(manifest, parent);
+    manifest
+        .lines()
+        .enumerate()
+        .skip(parent_line)
+        .take_while(|(_, line)| line.is_empty() || line.starts_with([' ', '-', '#']))
+        .filter_map(|(i, line)| {
+            let content = line.trim_start().trim_start_matches("- ");
+            let rest = content.strip_prefix(key)?.strip_prefix(':')?;
+            let parsed: String = serde_yaml::from_str(rest.trim()).ok()?;
+            if parsed != value {
+                return None;
+            }
+            Some((i + 1, line.len() - content.len() + 1))
+        })
+        .nth(nth)
+}
+
+// Top level keys start at the first column
+fn find_top_level_key(manifest: &str, key: &str) -> (usize, usize) {
+    manifest
+        .lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with(':'))
+        })
+        .map_or((1, 1), |i| (i + 1, 1))
+}
+
+struct Validator<'a> {
+    manifest: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, (line, column): (usize, usize), message: String) {
+        self.diagnostics.push(Diagnostic {
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn required_string<'v>(&mut self, manifest: &'v Mapping, key: &str) -> Option<&'v str> {
+        match manifest.get(key) {
+            None | Some(Value::Null) => {
+                self.report((1, 1), format!("missing required field `{key}`"));
+                None
+            }
+            Some(Value::String(value)) => Some(value),
+            Some(_) => {
+                let position = find_top_level_key(self.manifest, key);
+                self.report(position, format!("field `{key}` must be a string"));
+                None
+            }
+        }
+    }
+
+    fn check_services(&mut self, services: &[Value]) {
+        let mut names = BTreeSet::new();
+        let mut occurrences: Vec<&str> = vec![];
+
+        for (i, service) in services.iter().enumerate() {
+            let Value::Mapping(service) = service else {
+                let position = find_top_level_key(self.manifest, "services");
+                self.report(position, format!("service #{} must be a mapping", i + 1));
+                continue;
+            };
+
+            let Some(Value::String(name)) = service.get("name") else {
+                let position = find_top_level_key(self.manifest, "services");
+                self.report(
+                    position,
+                    format!("service #{} has no string field `name`", i + 1),
+                );
+                continue;
+            };
+            let nth = occurrences.iter().filter(|other| **other == name).count();
+            occurrences.push(name);
+            let position = find_nested_key(self.manifest, "services", "name", name, nth);
+
+            if name.is_empty() {
+                self.report(position, format!("service #{} has empty name", i + 1));
+            }
+            if !names.insert(name.as_str()) {
+                self.report(position, format!("duplicate service name `{name}`"));
+            }
+
+            match service.get("default_configuration") {
+                None | Some(Value::Null | Value::Mapping(_)) => {}
+                Some(_) => self.report(
+                    position,
+                    format!("`default_configuration` of service `{name}` must be a mapping"),
+                ),
+            }
+        }
+    }
+}
+
+/// Check the rendered `manifest.yaml` against the schema picodata expects
+/// on `CREATE PLUGIN`.
+pub fn validate(manifest: &str, pkg_version: &str) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        manifest,
+        diagnostics: vec![],
+    };
+
+    let value: Value = match serde_yaml::from_str(manifest) {
+        Ok(value) => value,
+        Err(e) => {
+            let (line, column) = e
+                .location()
+                .map_or((1, 1), |location| (location.line(), location.column()));
+            validator.report((line, column), format!("invalid YAML: {e}"));
+            return validator.diagnostics;
+        }
+    };
+
+    let Value::Mapping(value) = value else {
+        validator.report((1, 1), "manifest must be a mapping".into());
+        return validator.diagnostics;
+    };
+
+    validator.required_string(&value, "name");
+
+    if let Some(version) = validator.required_string(&value, "version") {
+        if version != pkg_version {
+            let position = find_top_level_key(manifest, "version");
+            validator.report(
+                position,
+                format!("version {version} does not match package version {pkg_version}"),
+            );
+        }
+    }
+
+    match value.get("services") {
+        None | Some(Value::Null) => {
+            validator.report((1, 1), "missing required field `services`".into());
+        }
+        Some(Value::Sequence(services)) => validator.check_services(services),
+        Some(_) => {
+            let position = find_top_level_key(manifest, "services");
+            validator.report(position, "field `services` must be a sequence".into());
+        }
+    }
+
+    validator.diagnostics
+}
+
+// `default_configuration` key with the value, indented to be
+// a key of the service mapping
+fn default_config_block(config: &Value, indent: usize) -> Result<String, String> {
+    let indent = " ".repeat(indent);
+    let yaml = serde_yaml::to_string(config).map_err(|e| e.to_string())?;
+    let yaml = yaml.trim_end();
+    if !yaml.contains('\n') {
+        return Ok(format!("{indent}default_configuration: {yaml}\n"));
+    }
+
+    let mut block = format!("{indent}default_configuration:\n");
+    for line in yaml.lines() {
+        block.push_str(&format!("{indent}  {line}\n"));
+    }
+    Ok(block)
+}
+
+/// Put default configurations of services into the rendered manifest.
+/// Configuration written in the template by hand must be the same.
+/// Only `default_configuration` lines are added after the `name` line of the service,
+/// the rest of the manifest is kept as written in the template.
+/// Returns `None` if there is nothing to inject or the manifest is malformed,
+/// which is reported by [`validate`].
+pub fn inject_default_configs(
+    manifest: &str,
+    configs: &BTreeMap<String, Value>,
+) -> Result<Option<String>, Vec<String>> {
+    if configs.is_empty() {
+        return Ok(None);
+    }
+    let Ok(value) = serde_yaml::from_str::<Value>(manifest) else {
+        return Ok(None);
+    };
+    let Some(Value::Sequence(services)) = value.get("services") else {
+        return Ok(None);
+    };
+
+    let mut problems = vec![];
+    let mut injected = BTreeSet::new();
+    // Text to insert after the line with the given number
+    let mut insertions = BTreeMap::new();
+    let mut occurrences: Vec<&str> = vec![];
+    for service in services.iter().filter_map(Value::as_mapping) {
+        let Some(name) = service.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let nth = occurrences.iter().filter(|other| **other == name).count();
+        occurrences.push(name);
+        let Some(config) = configs.get(name) else {
+            continue;
+        };
+        injected.insert(name.to_string());
+
+        match service.get("default_configuration") {
+            None => {
+                let Some((line, column)) =
+                    nested_key_position(manifest, "services", "name", name, nth)
+                else {
+                    problems.push(format!(
+                        "failed to find `name` of service `{name}` in the manifest to put `default_configuration` after it, write the service as a block mapping"
+                    ));
+                    continue;
+                };
+                match default_config_block(config, column - 1) {
+                    Ok(block) => {
+                        insertions.insert(line, block);
+                    }
+                    Err(e) => problems.push(format!(
+                        "failed to serialize default configuration of service `{name}`: {e}"
+                    )),
+                }
+            }
+            Some(template_config) if template_config == config => {}
+            Some(Value::Null) => problems.push(format!(
+                "`default_configuration` of service `{name}` is empty in the manifest template, remove it to use the one given to the build helper"
+            )),
+            Some(_) => problems.push(format!(
+                "`default_configuration` of service `{name}` in the manifest template differs from the one given to the build helper, remove it from the template"
+            )),
+        }
+    }
+
+    for name in configs.keys().filter(|name| !injected.contains(*name)) {
+        problems.push(format!(
+            "default configuration is given for service `{name}`, which is not in the manifest"
+        ));
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    if insertions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut injected_manifest = String::with_capacity(manifest.len());
+    for (i, line) in manifest.split_inclusive('\n').enumerate() {
+        injected_manifest.push_str(line);
+        if let Some(block) = insertions.get(&(i + 1)) {
+            if !line.ends_with('\n') {
+                injected_manifest.push('\n');
+            }
+            injected_manifest.push_str(block);
+        }
+    }
+
+    // Inserted lines could land into a multiline value of the template
+    let is_injected = serde_yaml::from_str::<Value>(&injected_manifest)
+        .ok()
+        .and_then(|value| value.get("services").and_then(Value::as_sequence).cloned())
+        .is_some_and(|injected_services| {
+            injected_services.len() == services.len()
+                && injected_services.iter().all(|service| {
+                    let name = service.get("name").and_then(Value::as_str);
+                    name.and_then(|name| configs.get(name))
+                        .is_none_or(|config| service.get("default_configuration") == Some(config))
+                })
+        });
+    if !is_injected {
+        return Err(vec![
+            "failed to put `default_configuration` of services into the manifest, write services as block mappings".into(),
+        ]);
+    }
+
+    Ok(Some(injected_manifest))
+}
+
+/// Default configuration of plugin services given to the build helper.
+#[derive(Debug, Clone)]
+pub(super) enum DefaultConfig {
+    /// Configuration of a single service serialized in `build.rs`
+    Service {
+        service: String,
+        config: Result<Value, String>,
+    },
+    /// YAML file like `plugin_config.yaml` with configurations by service names
+    File(PathBuf),
+}
+
+/// Collect default configurations by service names.
+pub(super) fn resolve_default_configs(
+    default_configs: &[DefaultConfig],
+) -> Result<BTreeMap<String, Value>, Vec<String>> {
+    let mut entries = vec![];
+    let mut problems = vec![];
+
+    for default_config in default_configs {
+        match default_config {
+            DefaultConfig::Service { service, config } => match config {
+                Ok(config) => entries.push((service.clone(), config.clone(), "build.rs".into())),
+                Err(e) => problems.push(format!(
+                    "failed to serialize default configuration of service `{service}`: {e}"
+                )),
+            },
+            DefaultConfig::File(path) => {
+                println!("cargo::rerun-if-changed={}", path.display());
+                let services = fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| {
+                        serde_yaml::from_str::<BTreeMap<String, Value>>(&content)
+                            .map_err(|e| e.to_string())
+                    });
+                match services {
+                    Ok(services) => entries.extend(
+                        services
+                            .into_iter()
+                            .map(|(service, config)| (service, config, path.display().to_string())),
+                    ),
+                    Err(e) => problems.push(format!(
+                        "failed to read default configurations from {}: {e}",
+                        path.display()
+                    )),
+                }
+            }
+        }
+    }
+
+    let mut configs = BTreeMap::new();
+    for (service, config, source) in entries {
+        if configs.insert(service.clone(), config).is_some() {
+            problems.push(format!(
+                "default configuration of service `{service}` from {source} is given twice"
+            ));
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    Ok(configs)
+}
@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::commands::migrations::{
+    checksum, migration_files, MigrationsLock, MIGRATIONS_DIR, MIGRATIONS_LOCK_NAME,
+};
+
+const UP_MARKER: &str = "-- pico.UP";
+const DOWN_MARKER: &str = "-- pico.DOWN";
+
+/// Migration file named like `0001_create_table.sql`.
+#[derive(Debug)]
+struct Migration {
+    number: u64,
+    /// Width of the numeric prefix, same for all migrations to keep them sorted
+    width: usize,
+    file_name: String,
+}
+
+impl Migration {
+    fn parse(file_name: &str) -> Option<Self> {
+        let stem = file_name.strip_suffix(".sql")?;
+        let (prefix, name) = stem.split_once('_')?;
+        if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_digit()) || name.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            number: prefix.parse().ok()?,
+            width: prefix.len(),
+            file_name: file_name.to_string(),
+        })
+    }
+}
+
+fn check_markers(path: &Path, problems: &mut Vec<String>) -> Result<()> {
+    let content = fs::read_to_string(path).context(format!("failed to read {}", path.display()))?;
+    let line_of = |marker: &str| content.lines().position(|line| line.trim() == marker);
+
+    match (line_of(UP_MARKER), line_of(DOWN_MARKER)) {
+        (Some(up), Some(down)) if up > down => problems.push(format!(
+            "{}: `{UP_MARKER}` must go before `{DOWN_MARKER}`",
+            path.display()
+        )),
+        (Some(_), Some(_)) => {}
+        (up, down) => {
+            for (marker, line) in [(UP_MARKER, up), (DOWN_MARKER, down)] {
+                if line.is_none() {
+                    problems.push(format!("{}: missing `{marker}` marker", path.display()));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check naming, order and markers of the plugin migrations
+/// and that released migrations from `migrations.lock` are not changed.
+pub(super) fn check(package_dir: &Path) -> Result<Vec<String>> {
+    let migrations_dir = package_dir.join(MIGRATIONS_DIR);
+    let mut problems = vec![];
+    let mut migrations = vec![];
+
+    let files = migration_files(package_dir)?;
+    for file_name in &files {
+        let path = migrations_dir.join(file_name);
+        match Migration::parse(file_name) {
+            Some(migration) if path.is_file() => {
+                check_markers(&path, &mut problems)?;
+                migrations.push(migration);
+            }
+            _ => problems.push(format!(
+                "{}: migration must be a file named like 0001_description.sql",
+                path.display()
+            )),
+        }
+    }
+
+    if let Some(first) = migrations.first() {
+        if migrations.iter().any(|m| m.width != first.width) {
+            problems.push(format!(
+                "{}: numeric prefixes of migrations must have the same width",
+                migrations_dir.display()
+            ));
+        }
+    }
+
+    let mut numbers = BTreeSet::new();
+    for migration in &migrations {
+        if !numbers.insert(migration.number) {
+            problems.push(format!(
+                "{}: duplicate migration number {}",
+                migrations_dir.join(&migration.file_name).display(),
+                migration.number
+            ));
+        }
+    }
+    for (expected, number) in (1..).zip(&numbers) {
+        if *number != expected {
+            problems.push(format!(
+                "{}: migration number {expected} is missing, numbers must go without gaps from 1",
+                migrations_dir.display()
+            ));
+            break;
+        }
+    }
+
+    let lock = MigrationsLock::load(package_dir)?;
+    for (file_name, locked_checksum) in &lock.migrations {
+        let path = migrations_dir.join(file_name);
+        if !path.exists() {
+            problems.push(format!(
+                "{}: released migration is removed, it is still listed in {MIGRATIONS_LOCK_NAME}",
+                path.display()
+            ));
+        } else if &checksum(&path)? != locked_checksum {
+            problems.push(format!(
+                "{}: released migration is changed, add a new migration instead",
+                path.display()
+            ));
+        }
+    }
+
+    Ok(problems)
+}
@@ -1,11 +1,32 @@
+use anyhow::{bail, Context, Result};
+use assets::{
+    add_custom_assets, add_generated_assets, add_included_files, CustomAsset, GeneratedAsset,
+};
+use build_info::{BuildInfo, BUILD_INFO_NAME};
 use derive_builder::Builder;
 use fs_extra::dir;
 use fs_extra::dir::CopyOptions;
+use manifest::DefaultConfig;
+use serde::Serialize;
 use std::env;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::commands::migrations::{check_context, MIGRATIONS_DIR, MIGRATIONS_LOCK_NAME};
+use crate::commands::run::Topology;
+
+mod assets;
+mod build_info;
+mod manifest;
+mod migrations;
 
 const MANIFEST_TEMPLATE_NAME: &str = "manifest.yaml.template";
+const TOPOLOGY_NAME: &str = "topology.toml";
+
+/// Directory of the built plugin library to put the plugin into,
+/// for layouts not derivable from `OUT_DIR`, e.g. with `--artifact-dir`
+pub const ARTIFACT_DIR_ENV: &str = "PIKE_ARTIFACT_DIR";
 
 #[cfg(target_os = "linux")]
 const LIB_EXT: &str = "so";
@@ -13,205 +34,466 @@ const LIB_EXT: &str = "so";
 #[cfg(target_os = "macos")]
 const LIB_EXT: &str = "dylib";
 
-// Get output path from env variable to get path up until debug/ or release/ folder
-fn get_output_path() -> PathBuf {
-    Path::new(&env::var("OUT_DIR").unwrap())
-        .ancestors()
-        .take(4)
-        .collect()
+// Get artifact dir of the build from `OUT_DIR`, which looks like
+// `<target-dir>/[<triple>/]<profile>/build/<package>-<hash>/out`,
+// so custom target dirs, profiles and targets are handled as well
+fn get_output_path() -> Result<PathBuf> {
+    println!("cargo::rerun-if-env-changed={ARTIFACT_DIR_ENV}");
+    if let Some(artifact_dir) = env::var_os(ARTIFACT_DIR_ENV) {
+        let artifact_dir = PathBuf::from(artifact_dir);
+        if artifact_dir.is_relative() {
+            bail!(
+                "{ARTIFACT_DIR_ENV} must be an absolute path, got {}",
+                artifact_dir.display()
+            );
+        }
+        return Ok(artifact_dir);
+    }
+
+    let out_dir = env::var("OUT_DIR").context("OUT_DIR is not set, is it run by cargo?")?;
+    let out_dir = Path::new(&out_dir);
+    let artifact_dir = Some(out_dir)
+        .filter(|dir| dir.ends_with("out"))
+        .and_then(Path::parent)
+        .and_then(Path::parent)
+        .filter(|dir| dir.ends_with("build"))
+        .and_then(Path::parent);
+
+    artifact_dir.map(Path::to_path_buf).context(format!(
+        "unknown layout of OUT_DIR {}, expected <target dir>/[<target triple>/]<profile>/build/<package>-<hash>/out, \
+        set {ARTIFACT_DIR_ENV} to the directory of the built plugin library",
+        out_dir.display()
+    ))
 }
 
 #[derive(Debug, Builder)]
 pub struct Params {
     #[builder(default)]
     #[builder(setter(custom))]
-    custom_assets: Vec<(PathBuf, PathBuf)>,
+    custom_assets: Vec<CustomAsset>,
+    #[builder(default)]
+    #[builder(setter(custom))]
+    generated_assets: Vec<GeneratedAsset>,
+    #[builder(default)]
+    #[builder(setter(custom))]
+    default_configs: Vec<DefaultConfig>,
 }
 
 impl ParamsBuilder {
+    fn add_custom_assets<I>(&mut self, assets: I) -> &mut Self
+    where
+        I: IntoIterator<Item = CustomAsset>,
+    {
+        let mut t = self.custom_assets.take().unwrap_or_default();
+        t.extend(assets);
+        self.custom_assets = Some(t);
+
+        self
+    }
+
+    /// Copy files into the `assets` folder by their file names.
+    /// Paths are relative to the plugin directory and can be globs like `config/*.yaml`,
+    /// missing files are skipped with a warning.
     pub fn custom_assets<I, S>(&mut self, assets: I) -> &mut Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
-        self.custom_assets_with_targets(assets.into_iter().map(|asset| {
-            (
-                asset.as_ref().to_string(),
-                Path::new(asset.as_ref())
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-            )
+        self.add_custom_assets(assets.into_iter().map(|asset| CustomAsset {
+            from: asset.as_ref().to_string(),
+            to: None,
+            required: false,
         }))
     }
 
+    /// Copy files into the given paths inside of the `assets` folder,
+    /// matches of a glob are copied into the given directory.
     pub fn custom_assets_with_targets<I, S>(&mut self, assets: I) -> &mut Self
     where
         I: IntoIterator<Item = (S, S)>,
         S: AsRef<str>,
     {
-        let custom_assets_with_targets: Vec<(PathBuf, PathBuf)> = assets
-            .into_iter()
-            .map(|(from, to)| (from.as_ref().into(), to.as_ref().into()))
-            .collect();
+        self.add_custom_assets(assets.into_iter().map(|(from, to)| CustomAsset {
+            from: from.as_ref().to_string(),
+            to: Some(to.as_ref().into()),
+            required: false,
+        }))
+    }
 
-        let mut t = self.custom_assets.take().unwrap_or_default();
-        t.extend(custom_assets_with_targets);
-        self.custom_assets = Some(t);
+    /// Same as [`Self::custom_assets`], but missing files fail the build.
+    pub fn required_assets<I, S>(&mut self, assets: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.add_custom_assets(assets.into_iter().map(|asset| CustomAsset {
+            from: asset.as_ref().to_string(),
+            to: None,
+            required: true,
+        }))
+    }
 
-        self
+    /// Same as [`Self::custom_assets_with_targets`], but missing files fail the build.
+    pub fn required_assets_with_targets<I, S>(&mut self, assets: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        self.add_custom_assets(assets.into_iter().map(|(from, to)| CustomAsset {
+            from: from.as_ref().to_string(),
+            to: Some(to.as_ref().into()),
+            required: true,
+        }))
     }
-}
 
-fn add_custom_assets(custom_assets: &Vec<(PathBuf, PathBuf)>, plugin_path: &Path) {
-    for (from_asset_path, to_asset_path) in custom_assets {
-        if !from_asset_path.exists() {
-            println!(
-                "cargo::warning=Couldn't find custom asset {} - skipping",
-                from_asset_path.display(),
-            );
+    /// Write contents returned by `generate` into the `to` path inside of the `assets` folder.
+    /// The asset is generated again when any of `inputs` changes.
+    pub fn generated_asset<P, I, S, F>(&mut self, to: P, inputs: I, generate: F) -> &mut Self
+    where
+        P: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        F: Fn() -> Result<Vec<u8>, Box<dyn std::error::Error>> + 'static,
+    {
+        let mut t = self.generated_assets.take().unwrap_or_default();
+        t.push(GeneratedAsset {
+            to: to.as_ref().into(),
+            inputs: inputs
+                .into_iter()
+                .map(|input| input.as_ref().into())
+                .collect(),
+            generate: Rc::new(generate),
+        });
+        self.generated_assets = Some(t);
 
-            continue;
-        }
+        self
+    }
 
-        // Check if `to_asset_path` is out of bounds of the `assets` folder
-        if to_asset_path.components().any(|comp| {
-            matches!(comp, std::path::Component::ParentDir)
-                || matches!(comp, std::path::Component::RootDir)
-        }) {
-            println!(
-                "cargo::warning=Path to a custom asset destination {} goes out of the assets folder - skipping",
-                to_asset_path.display()
-            );
+    /// Put `config` into `default_configuration` of the service in the rendered manifest,
+    /// for example the default value of the service config type.
+    pub fn service_default_config<T: Serialize>(&mut self, service: &str, config: &T) -> &mut Self {
+        let mut t = self.default_configs.take().unwrap_or_default();
+        t.push(DefaultConfig::Service {
+            service: service.to_string(),
+            config: serde_yaml::to_value(config).map_err(|e| e.to_string()),
+        });
+        self.default_configs = Some(t);
 
-            continue;
-        }
+        self
+    }
 
-        let destination = plugin_path.join("assets").join(to_asset_path);
+    /// Put default configurations of services from a YAML file like `plugin_config.yaml`
+    /// into the rendered manifest. The path is calculated from plugin directory.
+    pub fn default_configs_file<S: AsRef<str>>(&mut self, path: S) -> &mut Self {
+        let mut t = self.default_configs.take().unwrap_or_default();
+        t.push(DefaultConfig::File(path.as_ref().into()));
+        self.default_configs = Some(t);
 
-        println!("cargo::rerun-if-changed={}", from_asset_path.display());
+        self
+    }
+}
 
-        if from_asset_path.is_dir() {
-            if !destination.exists() {
-                fs::create_dir_all(&destination).unwrap();
-            }
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+// Rerun the build when files are added to the literal directory of the glob.
+// Globs at the root of the plugin are tracked by the matched files only,
+// the whole plugin directory includes `target`
+fn rerun_if_glob_changed(base_dir: &Path, glob: &str) {
+    let glob_dir: PathBuf = Path::new(glob)
+        .components()
+        .take_while(|component| match component {
+            Component::Normal(name) => !name.to_string_lossy().contains(GLOB_CHARS),
+            _ => false,
+        })
+        .collect();
 
-            let mut options = fs_extra::dir::CopyOptions::new();
-            options.overwrite = true;
-            options.copy_inside = true;
-            options.content_only = true;
-            fs_extra::dir::copy(from_asset_path, &destination, &options).unwrap();
-        } else {
-            // Create a directory into which a file should be copied
-            let parent_destination_directory = destination.parent().unwrap();
-            if !parent_destination_directory.exists() {
-                fs::create_dir_all(parent_destination_directory).unwrap();
-            }
-            fs::copy(from_asset_path, destination).unwrap();
-        }
+    let glob_dir = base_dir.join(glob_dir);
+    if glob_dir != base_dir && glob_dir.exists() {
+        println!("cargo::rerun-if-changed={}", glob_dir.display());
     }
 }
 
-pub fn main(params: &Params) {
-    let out_dir = get_output_path();
-    let pkg_version = env::var("CARGO_PKG_VERSION").unwrap();
-    let pkg_name = env::var("CARGO_PKG_NAME").unwrap();
-    let plugin_path = out_dir.join(&pkg_name).join(&pkg_version);
-    let out_manifest_path = plugin_path.join("manifest.yaml");
-    let lib_name = format!("lib{}.{LIB_EXT}", pkg_name.replace('-', "_"));
+// Cargo joins authors of the package with `:`
+fn package_authors() -> Vec<String> {
+    env::var("CARGO_PKG_AUTHORS")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|author| !author.is_empty())
+        .map(String::from)
+        .collect()
+}
 
-    dir::remove(&plugin_path).unwrap();
-    fs::create_dir_all(&plugin_path).unwrap();
+// Arbitrary values of `[package.metadata.pike.manifest]` for the manifest template
+fn manifest_metadata(crate_dir: &Path) -> Result<toml::Table> {
+    let manifest_path = crate_dir.join("Cargo.toml");
+    let manifest: toml::Table = toml::from_str(
+        &fs::read_to_string(&manifest_path)
+            .context(format!("failed to read {}", manifest_path.display()))?,
+    )
+    .context(format!("failed to parse {}", manifest_path.display()))?;
+
+    let Some(values) = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("pike"))
+        .and_then(|pike| pike.get("manifest"))
+    else {
+        return Ok(toml::Table::new());
+    };
+
+    values
+        .as_table()
+        .cloned()
+        .context("[package.metadata.pike.manifest] must be a table")
+}
 
-    // Iterate through plugins version to find the latest
-    // then replace symlinks with actual files
-    for plugin_version_entry in fs::read_dir(out_dir.join(&pkg_name)).unwrap() {
-        let plugin_version_path = plugin_version_entry.unwrap().path();
+// Replace symlinks to the library in older plugin versions with copies,
+// the library is rebuilt for the newest version only
+fn freeze_previous_versions(out_dir: &Path, pkg_name: &str, lib_name: &str) -> Result<()> {
+    let plugin_dir = out_dir.join(pkg_name);
+    for plugin_version_entry in fs::read_dir(&plugin_dir).context(format!(
+        "failed to read plugin directory {}",
+        plugin_dir.display()
+    ))? {
+        let plugin_version_path = plugin_version_entry?.path();
         if !plugin_version_path.is_dir() {
             continue;
         }
 
-        for plugin_artefact in fs::read_dir(&plugin_version_path).unwrap() {
-            let entry = plugin_artefact.unwrap();
-            if !entry.file_type().unwrap().is_symlink()
-                || (entry.file_name().to_str().unwrap() != lib_name)
-            {
+        for plugin_artefact in fs::read_dir(&plugin_version_path).context(format!(
+            "failed to read plugin directory {}",
+            plugin_version_path.display()
+        ))? {
+            let entry = plugin_artefact?;
+            if !entry.file_type()?.is_symlink() || entry.file_name() != lib_name {
                 continue;
             }
 
             // Need to remove symlink before copying in order to properly replace symlink with file
-            let plugin_lib_path = plugin_version_path.join(&lib_name);
+            let plugin_lib_path = plugin_version_path.join(lib_name);
             let _ = fs::remove_file(&plugin_lib_path);
-            fs::copy(out_dir.join(&lib_name), &plugin_lib_path).unwrap();
+            fs::copy(out_dir.join(lib_name), &plugin_lib_path).context(format!(
+                "failed to copy library to {}",
+                plugin_lib_path.display()
+            ))?;
 
             break;
         }
     }
 
+    Ok(())
+}
+
+// Warn about migration context placeholders without a value in `topology.toml`
+// of the plugin or of its workspace, and about unused variables
+fn lint_migration_context(crate_dir: &Path, pkg_name: &str) -> Result<()> {
+    let Some(topology_path) = crate_dir
+        .ancestors()
+        .take(2)
+        .map(|dir| dir.join(TOPOLOGY_NAME))
+        .find(|path| path.exists())
+    else {
+        return Ok(());
+    };
+    println!("cargo::rerun-if-changed={}", topology_path.display());
+
+    let content = fs::read_to_string(&topology_path)
+        .context(format!("failed to read {}", topology_path.display()))?;
+    let topology: Topology = match toml::from_str(&content) {
+        Ok(topology) => topology,
+        Err(e) => {
+            println!(
+                "cargo::warning=Failed to parse {}, skipping check of migration context: {}",
+                topology_path.display(),
+                e.message()
+            );
+            return Ok(());
+        }
+    };
+    let Some(plugin) = topology.plugins.get(pkg_name) else {
+        return Ok(());
+    };
+
+    let lint = check_context(crate_dir, &plugin.migration_context)?;
+    for problem in lint.missing {
+        println!("cargo::warning={problem} in {}", topology_path.display());
+    }
+    for problem in lint.unused {
+        println!(
+            "cargo::warning=plugin {pkg_name} in {}: {problem}",
+            topology_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn render_manifest(
+    params: &Params,
+    crate_dir: &Path,
+    migrations: &[String],
+    build_info: &BuildInfo,
+    out_manifest_path: &Path,
+) -> Result<()> {
+    let pkg_version = env::var("CARGO_PKG_VERSION").context("CARGO_PKG_VERSION is not set")?;
+    let template_path = crate_dir.join(MANIFEST_TEMPLATE_NAME);
+    let template = fs::read_to_string(&template_path)
+        .context(format!("failed to read {}", template_path.display()))?;
+    let template = liquid::ParserBuilder::with_stdlib()
+        .build()?
+        .parse(&template)
+        .context(format!(
+            "invalid manifest template {}",
+            template_path.display()
+        ))?;
+
+    let template_ctx = liquid::object!({
+        "name": env::var("CARGO_PKG_NAME").unwrap_or_default(),
+        "version": pkg_version,
+        "description": env::var("CARGO_PKG_DESCRIPTION").unwrap_or_default(),
+        "authors": package_authors(),
+        "license": env::var("CARGO_PKG_LICENSE").unwrap_or_default(),
+        "repository": env::var("CARGO_PKG_REPOSITORY").unwrap_or_default(),
+        "features": build_info.features,
+        "migrations": migrations,
+        "build_info": liquid::model::to_value(build_info)?,
+        "metadata": liquid::model::to_value(&manifest_metadata(crate_dir)?)?,
+    });
+
+    let mut manifest = template.render(&template_ctx).context(format!(
+        "failed to render manifest template {}",
+        template_path.display()
+    ))?;
+    let default_configs = manifest::resolve_default_configs(&params.default_configs)
+        .and_then(|configs| manifest::inject_default_configs(&manifest, &configs));
+    match default_configs {
+        Ok(Some(injected)) => manifest = injected,
+        Ok(None) => {}
+        Err(problems) => {
+            for problem in problems {
+                println!("cargo::error={problem}");
+            }
+        }
+    }
+    fs::write(out_manifest_path, &manifest)
+        .context(format!("failed to write {}", out_manifest_path.display()))?;
+
+    // Report problems of the rendered manifest before picodata rejects it
+    for diagnostic in manifest::validate(&manifest, &pkg_version) {
+        println!(
+            "cargo::error={}:{diagnostic} (rendered from {MANIFEST_TEMPLATE_NAME})",
+            out_manifest_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Store plugin artefacts in `<target-dir>/<profile>/<name>/<version>`,
+/// problems are returned as errors instead of panics.
+pub fn try_main(params: &Params) -> Result<()> {
+    let out_dir = get_output_path()?;
+    let pkg_version = env::var("CARGO_PKG_VERSION").context("CARGO_PKG_VERSION is not set")?;
+    let pkg_name = env::var("CARGO_PKG_NAME").context("CARGO_PKG_NAME is not set")?;
+    let plugin_path = out_dir.join(&pkg_name).join(&pkg_version);
+    let out_manifest_path = plugin_path.join("manifest.yaml");
+    let lib_name = format!("lib{}.{LIB_EXT}", pkg_name.replace('-', "_"));
+
+    dir::remove(&plugin_path).context(format!("failed to remove {}", plugin_path.display()))?;
+    fs::create_dir_all(&plugin_path)
+        .context(format!("failed to create {}", plugin_path.display()))?;
+
+    freeze_previous_versions(&out_dir, &pkg_name, &lib_name)?;
+
     // Generate folder with custom assets
-    fs::create_dir(plugin_path.join("assets")).unwrap();
+    fs::create_dir(plugin_path.join("assets")).context(format!(
+        "failed to create assets directory in {}",
+        plugin_path.display()
+    ))?;
 
     // Generate new manifest.yaml and migrations from template
-    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR is not set")?;
     let crate_dir = Path::new(&crate_dir);
 
-    let migrations_dir = crate_dir.join("migrations");
-    let mut migrations: Vec<String> = fs::read_dir(&migrations_dir)
-        .map(|dir| {
-            dir.map(|p| {
-                p.unwrap()
-                    .path()
-                    .strip_prefix(crate_dir)
-                    .unwrap()
-                    .to_string_lossy()
-                    .into()
-            })
-            .collect()
-        })
-        .unwrap_or_default();
+    let migrations_dir = crate_dir.join(MIGRATIONS_DIR);
+    let mut migrations: Vec<String> = vec![];
+    if migrations_dir.exists() {
+        for entry in fs::read_dir(&migrations_dir).context(format!(
+            "failed to read migrations directory {}",
+            migrations_dir.display()
+        ))? {
+            let path = entry?.path();
+            let path = path.strip_prefix(crate_dir).unwrap_or(&path);
+            migrations.push(path.to_string_lossy().into());
+        }
+    }
 
     migrations.sort();
 
+    for problem in migrations::check(crate_dir)? {
+        println!("cargo::error={problem}");
+    }
+    // Missing file would rerun the build script every time
+    if crate_dir.join(MIGRATIONS_LOCK_NAME).exists() {
+        println!("cargo::rerun-if-changed={MIGRATIONS_LOCK_NAME}");
+    }
+    lint_migration_context(crate_dir, &pkg_name)?;
+
     // Copy migrations directory and manifest into newest plugin version
     if !migrations.is_empty() {
         println!("cargo::rerun-if-changed={}", migrations_dir.display());
         let mut cp_opts = CopyOptions::new();
         cp_opts.overwrite = true;
-        dir::copy(&migrations_dir, &plugin_path, &cp_opts).unwrap();
+        dir::copy(&migrations_dir, &plugin_path, &cp_opts).context(format!(
+            "failed to copy migrations directory {}",
+            migrations_dir.display()
+        ))?;
     }
 
-    if crate_dir.join(MANIFEST_TEMPLATE_NAME).exists() {
-        let template_path = crate_dir.join(MANIFEST_TEMPLATE_NAME);
-        let template =
-            fs::read_to_string(template_path).expect("template for manifest plugin not found");
-        let template = liquid::ParserBuilder::with_stdlib()
-            .build()
-            .unwrap()
-            .parse(&template)
-            .expect("invalid manifest template");
-
-        let template_ctx = liquid::object!({
-            "version": pkg_version,
-            "migrations": migrations,
-        });
+    let build_info = BuildInfo::collect(crate_dir);
+    fs::write(
+        plugin_path.join(BUILD_INFO_NAME),
+        serde_json::to_string_pretty(&build_info)?,
+    )
+    .context(format!("failed to write {BUILD_INFO_NAME}"))?;
 
-        fs::write(&out_manifest_path, template.render(&template_ctx).unwrap()).unwrap();
+    if crate_dir.join(MANIFEST_TEMPLATE_NAME).exists() {
+        render_manifest(
+            params,
+            crate_dir,
+            &migrations,
+            &build_info,
+            &out_manifest_path,
+        )?;
     } else {
-        log::warn!(
-            "Couldn't find manifest.yaml template at {}, skipping its generation...",
+        println!(
+            "cargo::warning=Couldn't find {MANIFEST_TEMPLATE_NAME} at {}, skipping generation of manifest.yaml",
             crate_dir.display()
         );
     }
 
     // Create symlinks for newest plugin version, which would be created after build.rs script
-    std::os::unix::fs::symlink(out_dir.join(&lib_name), plugin_path.join(lib_name)).unwrap();
+    let lib_path = plugin_path.join(&lib_name);
+    std::os::unix::fs::symlink(out_dir.join(&lib_name), &lib_path)
+        .context(format!("failed to create symlink {}", lib_path.display()))?;
 
-    add_custom_assets(&params.custom_assets, &plugin_path);
+    add_custom_assets(&params.custom_assets, &plugin_path)?;
+    add_generated_assets(&params.generated_assets, &plugin_path)?;
+    add_included_files(crate_dir, &plugin_path)?;
 
     // Trigger on Cargo.toml change in order not to run cargo update each time
     // version is changed
     println!("cargo::rerun-if-changed=Cargo.toml");
     println!("cargo::rerun-if-changed={MANIFEST_TEMPLATE_NAME}");
+
+    Ok(())
+}
+
+/// Same as [`try_main`], but errors are reported as `cargo::error` diagnostics
+/// failing the build.
+pub fn main(params: &Params) {
+    if let Err(e) = try_main(params) {
+        // Diagnostic takes a single line
+        println!("cargo::error={}", format!("{e:#}").replace('\n', " "));
+    }
 }
@@ -5,6 +5,10 @@ pub mod cluster {
     pub use crate::commands::run::cluster as run;
     pub use crate::commands::run::ParamsBuilder as RunParamsBuilder;
 
+    pub use crate::commands::instance_log::LogFilter;
+    pub use crate::commands::instance_log::LogLevel;
+    pub use crate::commands::instance_log::LogRotation;
+    pub use crate::commands::lib::CargoOptions;
     pub use crate::commands::run::MigrationContextVar;
     pub use crate::commands::run::PicodataInstance;
     pub use crate::commands::run::PicodataInstanceProperties;
@@ -13,6 +17,10 @@ pub mod cluster {
     pub use crate::commands::run::Tier;
     pub use crate::commands::run::Topology;
 
+    pub use crate::commands::lua::eval as lua;
+    pub use crate::commands::lua::ParamsBuilder as LuaParamsBuilder;
+    pub use crate::commands::lua::Target as LuaTarget;
+
     pub use crate::commands::stop::cmd as stop;
     pub use crate::commands::stop::ParamsBuilder as StopParamsBuilder;
 }
@@ -21,6 +29,9 @@ pub mod config {
     pub use crate::commands::config::apply::cmd as apply;
     pub use crate::commands::config::apply::ConfigMap as PluginConfigMap;
     pub use crate::commands::config::apply::ParamsBuilder as ApplyParamsBuilder;
+    pub use crate::commands::config::get::get;
+    pub use crate::commands::config::get::ParamsBuilder as GetParamsBuilder;
+    pub use crate::commands::config::schema::{config_schema, validate, ConfigLint};
 }
 
 pub mod helpers;
@@ -4,6 +4,7 @@ use log::warn;
 use nix::unistd::{fork, ForkResult};
 use std::{
     env, fs,
+    os::fd::AsRawFd,
     path::{Path, PathBuf},
     process, thread,
     time::Duration,
@@ -12,6 +13,8 @@ use toml_edit::{DocumentMut, Item, Value};
 
 mod commands;
 
+use commands::output::{emit, error_code, ErrorCode, Event, MessageFormat};
+
 const CK_CHECK_PARRENT_INTERVAL_SEC: u64 = 3;
 
 // Translation of work Pike can also mean Щука.
@@ -65,10 +68,63 @@ const HUNGRY_SHARK: &str = r"
     disable_help_subcommand = true
 )]
 struct Cli {
+    /// Format of messages printed by pike
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "human",
+        visible_alias = "message-format"
+    )]
+    format: MessageFormat,
     #[command(subcommand)]
     command: Command,
 }
 
+/// Options passed through to `cargo build`
+#[derive(clap::Args, Debug)]
+struct CargoArgs {
+    /// Comma separated list of features to activate
+    #[arg(long, short = 'F', value_delimiter = ',')]
+    features: Vec<String>,
+    /// Activate all available features
+    #[arg(long)]
+    all_features: bool,
+    /// Do not activate the `default` feature
+    #[arg(long)]
+    no_default_features: bool,
+    /// Build with the given cargo profile
+    #[arg(long, value_name = "PROFILE-NAME")]
+    profile: Option<String>,
+    /// Assert that `Cargo.lock` will remain unchanged
+    #[arg(long)]
+    locked: bool,
+    /// Run cargo without accessing the network
+    #[arg(long)]
+    offline: bool,
+    /// Build for the target triple
+    #[arg(long, value_name = "TRIPLE")]
+    target: Option<String>,
+    /// Arguments passed to `cargo build` as is
+    #[arg(last = true, value_name = "CARGO_ARGS")]
+    cargo_args: Vec<String>,
+}
+
+impl From<CargoArgs> for commands::lib::CargoOptions {
+    fn from(args: CargoArgs) -> Self {
+        Self {
+            features: args.features,
+            all_features: args.all_features,
+            no_default_features: args.no_default_features,
+            profile: args.profile,
+            locked: args.locked,
+            offline: args.offline,
+            target: args.target,
+            extra_args: args.cargo_args,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Command {
     /// Run Picodata cluster
@@ -92,11 +148,11 @@ enum Command {
         #[arg(long, value_name = "BINARY_PATH", default_value = "picodata")]
         picodata_path: PathBuf,
         /// Run release version of plugin
-        #[arg(long)]
+        #[arg(long, conflicts_with = "profile")]
         release: bool,
-        /// Change target folder
-        #[arg(long, value_name = "TARGET_DIR", default_value = "target")]
-        target_dir: PathBuf,
+        /// Change target folder, cargo's target directory by default
+        #[arg(long, value_name = "TARGET_DIR")]
+        target_dir: Option<PathBuf>,
         /// Run cluster in background
         #[arg(long, short)]
         daemon: bool,
@@ -112,6 +168,32 @@ enum Command {
         /// Path to picodata config file
         #[arg(long, value_name = "CONFIG_PATH", default_value = "./picodata.yaml")]
         config_path: PathBuf,
+        /// Print only instance logs of this level or more severe
+        #[arg(long, value_enum, value_name = "LEVEL")]
+        log_level: Option<commands::instance_log::LogLevel>,
+        /// Print only instance log lines matching the regex
+        #[arg(long, value_name = "REGEX")]
+        log_grep: Option<regex::Regex>,
+        /// Print logs only of these instances, comma separated
+        #[arg(long, value_name = "INSTANCE_NAMES", value_delimiter = ',')]
+        log_instances: Vec<String>,
+        /// Do not print logs of these instances, comma separated
+        #[arg(long, value_name = "INSTANCE_NAMES", value_delimiter = ',')]
+        quiet_instances: Vec<String>,
+        /// Rotate instance log files once they grow over this size, e.g. `100M`
+        #[arg(long, value_name = "SIZE", value_parser = commands::instance_log::parse_size)]
+        log_max_size: Option<u64>,
+        /// Rotate instance log files once they are older than this interval, e.g. `1h`
+        #[arg(long, value_name = "INTERVAL", value_parser = commands::instance_log::parse_interval)]
+        log_rotate_interval: Option<Duration>,
+        /// Number of rotated log files to keep for each instance
+        #[arg(long, value_name = "COUNT")]
+        log_retention: Option<usize>,
+        /// How long to wait for all instances to become Online, e.g. `5m`
+        #[arg(long, value_name = "INTERVAL", default_value = "120s", value_parser = commands::instance_log::parse_interval)]
+        ready_timeout: Duration,
+        #[command(flatten)]
+        cargo: CargoArgs,
     },
     /// Stop Picodata cluster
     Stop {
@@ -145,6 +227,36 @@ enum Command {
         #[arg(long, value_name = "BINARY_PATH", default_value = "picodata")]
         picodata_path: PathBuf,
     },
+    /// Evaluate Lua code on instances of the running cluster
+    Lua {
+        /// Lua expression or chunk to evaluate. Example value: `box.info`
+        #[arg(value_name = "EXPR", required_unless_present = "file")]
+        expr: Option<String>,
+        /// Path to a Lua file to evaluate instead of the expression
+        #[arg(short, long, value_name = "FILE", conflicts_with = "expr")]
+        file: Option<PathBuf>,
+        /// Name of the instance to evaluate on, can be repeated
+        #[arg(long, value_name = "INSTANCE_NAME", group = "target")]
+        instance: Vec<String>,
+        /// Evaluate on every instance of the tier
+        #[arg(long, value_name = "TIER", group = "target")]
+        tier: Option<String>,
+        /// Evaluate on every instance of the cluster, the default target
+        #[arg(long, group = "target")]
+        all: bool,
+        /// Format of collected results [default: yaml, json with `--format json`]
+        #[arg(long, value_enum)]
+        output: Option<commands::lua::OutputFormat>,
+        /// Path to data directory of the cluster
+        #[arg(long, value_name = "DATA_DIR", default_value = "./tmp")]
+        data_dir: PathBuf,
+        /// Path to the plugin's project directory
+        #[arg(long, value_name = "PLUGIN_PATH", default_value = "./")]
+        plugin_path: PathBuf,
+        /// Specify path to picodata binary
+        #[arg(long, value_name = "BINARY_PATH", default_value = "picodata")]
+        picodata_path: PathBuf,
+    },
     /// Helpers for work with plugins
     Plugin {
         #[command(subcommand)]
@@ -162,26 +274,57 @@ enum Plugin {
     /// Pack your plugin into a distributable bundle
     Pack {
         /// Pack the archive with debug version of plugin
-        #[arg(long)]
+        #[arg(long, conflicts_with = "profile")]
         debug: bool,
-        /// Change target folder
-        #[arg(long, value_name = "TARGET_DIR", default_value = "target")]
-        target_dir: PathBuf,
+        /// Change target folder, cargo's target directory by default
+        #[arg(long, value_name = "TARGET_DIR")]
+        target_dir: Option<PathBuf>,
         /// Path to the plugin's project directory
         #[arg(long, value_name = "PLUGIN_PATH", default_value = "./")]
         plugin_path: PathBuf,
+        /// Path of the archive, or directory to put the archives into
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Format of the archive
+        #[arg(long, value_enum, default_value_t)]
+        archive_format: commands::plugin::pack::ArchiveFormat,
+        /// Compression level: 0-9 for tar.gz and zip, 1-22 for tar.zst
+        #[arg(long, value_name = "LEVEL")]
+        compression_level: Option<i32>,
+        /// Pack only the given workspace plugin, can be repeated
+        #[arg(long, short, value_name = "SPEC")]
+        package: Vec<String>,
+        /// Pack all selected plugins into a single archive
+        #[arg(long)]
+        bundle: bool,
+        /// Strip the packed library and write its debug info into `<name>-<version>-debug.tar.gz`
+        #[arg(long)]
+        split_debuginfo: bool,
+        /// Record checksums of the packed migrations in `migrations.lock`
+        #[arg(long)]
+        lock_migrations: bool,
+        #[command(flatten)]
+        cargo: CargoArgs,
+    },
+    /// Check the packed plugin archive before shipping
+    Verify {
+        /// Path to the archive created by `plugin pack`
+        #[arg(value_name = "ARCHIVE")]
+        archive: PathBuf,
     },
     /// Alias for cargo build command
     Build {
-        /// Change target folder
-        #[arg(long, value_name = "TARGET_DIR", default_value = "target")]
-        target_dir: PathBuf,
+        /// Change target folder, cargo's target directory by default
+        #[arg(long, value_name = "TARGET_DIR")]
+        target_dir: Option<PathBuf>,
         /// Build release version of plugin
-        #[arg(long, short)]
+        #[arg(long, short, conflicts_with = "profile")]
         release: bool,
         /// Path to the plugin's project directory
         #[arg(long, value_name = "PLUGIN_PATH", default_value = "./")]
         plugin_path: PathBuf,
+        #[command(flatten)]
+        cargo: CargoArgs,
     },
     /// Create a new Picodata plugin
     New {
@@ -234,6 +377,39 @@ enum Config {
         /// Choose plugin which config should be applied
         #[arg(long, value_name = "PLUGIN_NAME")]
         plugin_name: Option<String>,
+        /// Print the difference with the config on the cluster without applying it
+        #[arg(long)]
+        dry_run: bool,
+        /// Reset keys missing from the config to their default values from the manifest
+        #[arg(long)]
+        prune: bool,
+        /// Apply the config without checking it against default configuration in the manifest
+        #[arg(long)]
+        skip_validation: bool,
+    },
+    /// Print services config of the plugin from Picodata cluster started by the Run command
+    Get {
+        /// Path to data directory of the cluster
+        #[arg(long, value_name = "DATA_DIR", default_value = "./tmp")]
+        data_dir: PathBuf,
+        /// Path to the plugin's project directory
+        #[arg(long, value_name = "PLUGIN_PATH", default_value = "./")]
+        plugin_path: PathBuf,
+        /// Choose plugin which config should be printed, required in workspace
+        #[arg(long, value_name = "PLUGIN_NAME")]
+        plugin_name: Option<String>,
+        /// Print config of the single service
+        #[arg(long, value_name = "SERVICE")]
+        service: Option<String>,
+    },
+    /// Print JSON Schema of the plugin config inferred from default configuration in the manifest
+    Schema {
+        /// Path to the plugin's project directory
+        #[arg(long, value_name = "PLUGIN_PATH", default_value = "./")]
+        plugin_path: PathBuf,
+        /// Choose plugin which config schema should be printed, required in workspace
+        #[arg(long, value_name = "PLUGIN_NAME")]
+        plugin_name: Option<String>,
     },
 }
 
@@ -255,6 +431,19 @@ fn run_child_killer() {
         libc::setsid();
     }
 
+    // Do not keep stdio of pike open, otherwise readers of its output
+    // would wait for the supervisor to exit
+    if let Ok(dev_null) = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+    {
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            // SAFETY: both descriptors are open, `fd` is replaced atomically
+            unsafe { libc::dup2(dev_null.as_raw_fd(), fd) };
+        }
+    }
+
     let master_pid = i32::try_from(master_pid).expect("Master PID to big");
 
     loop {
@@ -274,6 +463,7 @@ fn is_required_path_exists(
     plugin_dir: &Path,
     required_path: &Path,
     error_message: &str,
+    error_code: ErrorCode,
     exit_code: i32,
 ) {
     if required_path.exists() {
@@ -283,35 +473,42 @@ fn is_required_path_exists(
         return;
     }
 
-    println!("{error_message}");
+    if commands::output::is_json() {
+        emit(&Event::Error {
+            code: error_code,
+            message: &format!(
+                "couldn't find {} in {}",
+                required_path.display(),
+                plugin_dir.display()
+            ),
+        });
+    } else {
+        println!("{error_message}");
+    }
     process::exit(exit_code);
 }
 
 // Add new member to Cargo.toml, additionally checks proper
 // environment for `plugin add` command
 fn modify_workspace(plugin_name: &str, plugin_path: &Path) -> Result<()> {
-    let cargo_toml_path = plugin_path.join("Cargo.toml");
-
-    let content = fs::read_to_string(&cargo_toml_path)?;
-    let mut doc = content.parse::<DocumentMut>()?;
-
-    let workspace = doc.get("workspace").and_then(Item::as_table);
-    if workspace.is_none() {
+    let project = commands::project::Project::load(plugin_path)?;
+    if !project.is_workspace || project.path != project.workspace_root {
         bail!("You are trying to add plugin outside of workspace directory");
     }
 
-    let workspace = doc["workspace"].as_table_mut().unwrap();
-
-    let already_exists = workspace
-        .get("members")
-        .and_then(Item::as_value)
-        .and_then(Value::as_array)
-        .is_some_and(|members| members.iter().any(|v| v.as_str() == Some(plugin_name)));
-
-    if already_exists {
+    if project.package(plugin_name).is_some() || project.path.join(plugin_name).exists() {
         bail!("Plugin with this name already exists");
     }
 
+    let cargo_toml_path = project.workspace_root.join("Cargo.toml");
+
+    let content = fs::read_to_string(&cargo_toml_path)?;
+    let mut doc = content.parse::<DocumentMut>()?;
+
+    let workspace = doc["workspace"]
+        .as_table_mut()
+        .context("workspace table can't be found")?;
+
     let members = workspace
         .get_mut("members")
         .and_then(Item::as_value_mut)
@@ -325,12 +522,27 @@ fn modify_workspace(plugin_name: &str, plugin_path: &Path) -> Result<()> {
     Ok(())
 }
 
-#[allow(clippy::too_many_lines)]
-fn main() -> Result<()> {
+fn main() {
     colog::init();
     let cli = Cli::parse_from(env::args().skip(1));
+    commands::output::set_message_format(cli.format);
+
+    if let Err(err) = run_command(cli.command) {
+        if commands::output::is_json() {
+            emit(&Event::Error {
+                code: error_code(&err),
+                message: &format!("{err:#}"),
+            });
+        } else {
+            eprintln!("Error: {err:?}");
+        }
+        process::exit(1);
+    }
+}
 
-    match cli.command {
+#[allow(clippy::too_many_lines)]
+fn run_command(command: Command) -> Result<()> {
+    match command {
         Command::Run {
             topology,
             data_dir,
@@ -345,8 +557,23 @@ fn main() -> Result<()> {
             plugin_path,
             no_build,
             config_path,
+            log_level,
+            log_grep,
+            log_instances,
+            quiet_instances,
+            log_max_size,
+            log_rotate_interval,
+            log_retention,
+            ready_timeout,
+            cargo,
         } => {
-            is_required_path_exists(&plugin_path, &topology, CARING_PIKE, 1);
+            is_required_path_exists(
+                &plugin_path,
+                &topology,
+                CARING_PIKE,
+                ErrorCode::PluginDirNotFound,
+                1,
+            );
 
             if !daemon {
                 run_child_killer();
@@ -354,19 +581,37 @@ fn main() -> Result<()> {
 
             // Parse topology toml file and validate the fields
             // Emit warning upon meeting alien fields
-            let topology: commands::run::Topology = serde_ignored::deserialize(
+            let mut topology: commands::run::Topology = serde_ignored::deserialize(
                 toml::de::Deserializer::new(
-                    &fs::read_to_string(plugin_path.join(&topology))
-                        .context(format!("failed to read {}", &topology.display()))?,
+                    &fs::read_to_string(plugin_path.join(&topology)).map_err(|e| {
+                        commands::output::coded(
+                            ErrorCode::InvalidTopology,
+                            format!("failed to read {}: {e}", &topology.display()),
+                        )
+                    })?,
                 ),
                 |path| {
                     warn!("Unknown field {path}");
                 },
             )
-            .context(format!(
-                "failed to parse .toml file of {}",
-                topology.display()
-            ))?;
+            .map_err(|e| {
+                commands::output::coded(
+                    ErrorCode::InvalidTopology,
+                    format!("failed to parse .toml file of {}: {e}", topology.display()),
+                )
+            })?;
+
+            // Command line arguments take precedence over topology
+            let log_rotation = &mut topology.log_rotation;
+            if log_max_size.is_some() {
+                log_rotation.max_size = log_max_size;
+            }
+            if log_rotate_interval.is_some() {
+                log_rotation.rotate_interval = log_rotate_interval;
+            }
+            if let Some(retention) = log_retention {
+                log_rotation.retention = retention;
+            }
 
             let params = commands::run::ParamsBuilder::default()
                 .topology(topology)
@@ -382,6 +627,14 @@ fn main() -> Result<()> {
                 .plugin_path(plugin_path)
                 .no_build(no_build)
                 .config_path(config_path)
+                .log_filter(commands::instance_log::LogFilter {
+                    level: log_level,
+                    grep: log_grep,
+                    instances: log_instances,
+                    quiet_instances,
+                })
+                .cargo_options(cargo.into())
+                .ready_timeout(ready_timeout)
                 .build()
                 .unwrap();
             commands::run::cmd(&params).context("failed to execute Run command")?;
@@ -390,7 +643,13 @@ fn main() -> Result<()> {
             data_dir,
             plugin_path,
         } => {
-            is_required_path_exists(&plugin_path, &data_dir, CARING_PIKE, 1);
+            is_required_path_exists(
+                &plugin_path,
+                &data_dir,
+                CARING_PIKE,
+                ErrorCode::PluginDirNotFound,
+                1,
+            );
 
             run_child_killer();
             let params = commands::stop::ParamsBuilder::default()
@@ -404,7 +663,13 @@ fn main() -> Result<()> {
             data_dir,
             plugin_path,
         } => {
-            is_required_path_exists(&plugin_path, &data_dir, HUNGRY_SHARK, 0);
+            is_required_path_exists(
+                &plugin_path,
+                &data_dir,
+                HUNGRY_SHARK,
+                ErrorCode::NothingToClean,
+                0,
+            );
 
             run_child_killer();
             commands::clean::cmd(&data_dir, &plugin_path)
@@ -416,12 +681,67 @@ fn main() -> Result<()> {
             plugin_path,
             picodata_path,
         } => {
-            is_required_path_exists(&plugin_path, &data_dir, CARING_PIKE, 1);
+            is_required_path_exists(
+                &plugin_path,
+                &data_dir,
+                CARING_PIKE,
+                ErrorCode::PluginDirNotFound,
+                1,
+            );
 
             run_child_killer();
             commands::enter::cmd(&instance_name, &data_dir, &plugin_path, &picodata_path)
                 .context("failed to execute \"enter\" command")?;
         }
+        Command::Lua {
+            expr,
+            file,
+            instance,
+            tier,
+            all,
+            output,
+            data_dir,
+            plugin_path,
+            picodata_path,
+        } => {
+            is_required_path_exists(
+                &plugin_path,
+                &data_dir,
+                CARING_PIKE,
+                ErrorCode::PluginDirNotFound,
+                1,
+            );
+
+            run_child_killer();
+            let target = if all {
+                commands::lua::Target::All
+            } else if !instance.is_empty() {
+                commands::lua::Target::Instances(instance)
+            } else if let Some(tier) = tier {
+                commands::lua::Target::Tier(tier)
+            } else {
+                commands::lua::Target::default()
+            };
+
+            let mut params = commands::lua::ParamsBuilder::default();
+            if let Some(file) = file {
+                params.file(file);
+            } else {
+                params.code(expr.unwrap_or_default());
+            }
+            let params = params
+                .target(target)
+                .output_format(output.unwrap_or(match commands::output::message_format() {
+                    MessageFormat::Human => commands::lua::OutputFormat::Yaml,
+                    MessageFormat::Json => commands::lua::OutputFormat::Json,
+                }))
+                .data_dir(data_dir)
+                .plugin_path(plugin_path)
+                .picodata_path(picodata_path)
+                .build()
+                .unwrap();
+            commands::lua::cmd(&params).context("failed to execute \"lua\" command")?;
+        }
         Command::Plugin { command } => {
             run_child_killer();
             match command {
@@ -429,21 +749,63 @@ fn main() -> Result<()> {
                     debug,
                     target_dir,
                     plugin_path,
+                    output,
+                    archive_format,
+                    compression_level,
+                    package,
+                    bundle,
+                    split_debuginfo,
+                    lock_migrations,
+                    cargo,
                 } => {
-                    is_required_path_exists(&plugin_path, Path::new("Cargo.toml"), CARING_PIKE, 1);
-
-                    commands::plugin::pack::cmd(debug, &target_dir, &plugin_path)
+                    is_required_path_exists(
+                        &plugin_path,
+                        Path::new("Cargo.toml"),
+                        CARING_PIKE,
+                        ErrorCode::PluginDirNotFound,
+                        1,
+                    );
+
+                    let params = commands::plugin::pack::ParamsBuilder::default()
+                        .debug(debug)
+                        .target_dir(target_dir)
+                        .plugin_path(plugin_path)
+                        .output(output)
+                        .format(archive_format)
+                        .compression_level(compression_level)
+                        .packages(package)
+                        .bundle(bundle)
+                        .split_debuginfo(split_debuginfo)
+                        .lock_migrations(lock_migrations)
+                        .cargo_options(cargo.into())
+                        .build()
+                        .unwrap();
+                    commands::plugin::pack::cmd(&params)
                         .context("failed to execute \"pack\" command")?;
                 }
+                Plugin::Verify { archive } => commands::plugin::verify::cmd(&archive)
+                    .context("failed to execute \"plugin verify\" command")?,
                 Plugin::Build {
                     release,
                     target_dir,
                     plugin_path,
+                    cargo,
                 } => {
-                    is_required_path_exists(&plugin_path, Path::new("Cargo.toml"), CARING_PIKE, 1);
-
-                    commands::plugin::build::cmd(release, &target_dir, &plugin_path)
-                        .context("failed to execute \"build\" command")?;
+                    is_required_path_exists(
+                        &plugin_path,
+                        Path::new("Cargo.toml"),
+                        CARING_PIKE,
+                        ErrorCode::PluginDirNotFound,
+                        1,
+                    );
+
+                    commands::plugin::build::cmd(
+                        release,
+                        target_dir.as_deref(),
+                        &plugin_path,
+                        &cargo.into(),
+                    )
+                    .context("failed to execute \"build\" command")?;
                 }
                 Plugin::New {
                     path,
@@ -457,7 +819,13 @@ fn main() -> Result<()> {
                 } => commands::plugin::new::cmd(None, without_git, workspace)
                     .context("failed to execute \"init\" command")?,
                 Plugin::Add { path, plugin_path } => {
-                    is_required_path_exists(&plugin_path, Path::new("Cargo.toml"), CARING_PIKE, 1);
+                    is_required_path_exists(
+                        &plugin_path,
+                        Path::new("Cargo.toml"),
+                        CARING_PIKE,
+                        ErrorCode::PluginDirNotFound,
+                        1,
+                    );
 
                     modify_workspace(path.file_name().unwrap().to_str().unwrap(), &plugin_path)
                         .context("failed to add new plugin to workspace")?;
@@ -470,27 +838,59 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Command::Config { command } => {
-            run_child_killer();
-            match command {
-                Config::Apply {
-                    config_path,
-                    data_dir,
-                    plugin_path,
-                    plugin_name,
-                } => {
-                    let params = commands::config::apply::ParamsBuilder::default()
-                        .config_path(config_path)
-                        .data_dir(data_dir)
-                        .plugin_path(plugin_path)
-                        .plugin_name(plugin_name)
-                        .build()
-                        .unwrap();
-                    commands::config::apply::cmd(&params)
-                        .context("failed to execute \"config apply\" command")?;
-                }
+        Command::Config { command } => match command {
+            Config::Apply {
+                config_path,
+                data_dir,
+                plugin_path,
+                plugin_name,
+                dry_run,
+                prune,
+                skip_validation,
+            } => {
+                run_child_killer();
+                let params = commands::config::apply::ParamsBuilder::default()
+                    .config_path(config_path)
+                    .data_dir(data_dir)
+                    .plugin_path(plugin_path)
+                    .plugin_name(plugin_name)
+                    .dry_run(dry_run)
+                    .prune(prune)
+                    .skip_validation(skip_validation)
+                    .build()
+                    .unwrap();
+                commands::config::apply::cmd(&params)
+                    .context("failed to execute \"config apply\" command")?;
             }
-        }
+            Config::Get {
+                data_dir,
+                plugin_path,
+                plugin_name,
+                service,
+            } => {
+                let params = commands::config::get::ParamsBuilder::default()
+                    .data_dir(data_dir)
+                    .plugin_path(plugin_path)
+                    .plugin_name(plugin_name)
+                    .service(service)
+                    .build()
+                    .unwrap();
+                commands::config::get::cmd(&params)
+                    .context("failed to execute \"config get\" command")?;
+            }
+            Config::Schema {
+                plugin_path,
+                plugin_name,
+            } => {
+                let params = commands::config::schema::ParamsBuilder::default()
+                    .plugin_path(plugin_path)
+                    .plugin_name(plugin_name)
+                    .build()
+                    .unwrap();
+                commands::config::schema::cmd(&params)
+                    .context("failed to execute \"config schema\" command")?;
+            }
+        },
     };
 
     Ok(())
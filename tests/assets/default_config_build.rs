@@ -0,0 +1,13 @@
+use pike::helpers::build;
+use std::collections::BTreeMap;
+
+fn main() {
+    let params = build::ParamsBuilder::default()
+        .service_default_config(
+            "example_service",
+            &BTreeMap::from([("value", "from build.rs")]),
+        )
+        .build()
+        .unwrap();
+    build::main(&params);
+}
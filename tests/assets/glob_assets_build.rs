@@ -0,0 +1,14 @@
+use pike::helpers::build;
+
+fn main() {
+    let params = build::ParamsBuilder::default()
+        .custom_assets(["migrations/*.sql"])
+        .custom_assets_with_targets([("src/*.rs", "sources")])
+        .required_assets(["plugin_config.yaml"])
+        .generated_asset("generated/version.txt", ["Cargo.toml"], || {
+            Ok(env!("CARGO_PKG_VERSION").as_bytes().to_vec())
+        })
+        .build()
+        .unwrap();
+    build::main(&params);
+}
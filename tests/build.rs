@@ -1,7 +1,12 @@
 mod helpers;
 
-use helpers::{assert_path_existance, build_plugin, init_plugin, validate_symlink, LIB_EXT};
+use helpers::{
+    assert_path_existance, build_plugin, exec_pike, init_plugin, validate_symlink, LIB_EXT,
+};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::path::Path;
+use std::process::Command;
 
 #[test]
 fn test_cargo_build() {
@@ -30,6 +35,254 @@ fn test_cargo_build() {
     assert_plugin_build_artefacts(&build_path.join("0.1.1"), true);
 }
 
+#[test]
+fn test_cargo_build_custom_profile() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-profile");
+
+    init_plugin("test-plugin-profile");
+
+    let mut cargo_toml = OpenOptions::new()
+        .append(true)
+        .open(plugin_path.join("Cargo.toml"))
+        .unwrap();
+    writeln!(cargo_toml, "\n[profile.custom]\ninherits = \"release\"").unwrap();
+
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        "test-plugin-profile",
+        "--profile",
+        "custom",
+    ]);
+
+    let artifact_dir = plugin_path.join("target").join("custom");
+    assert_path_existance(
+        &artifact_dir
+            .join("test-plugin-profile")
+            .join("0.1.0")
+            .join("manifest.yaml"),
+        false,
+    );
+    assert_path_existance(
+        &artifact_dir.join("test-plugin-profile-0.1.0.tar.gz"),
+        false,
+    );
+}
+
+#[test]
+fn test_cargo_build_manifest_context() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-context");
+
+    init_plugin("test-plugin-context");
+
+    let mut cargo_toml = OpenOptions::new()
+        .append(true)
+        .open(plugin_path.join("Cargo.toml"))
+        .unwrap();
+    writeln!(
+        cargo_toml,
+        "\n[package.metadata.pike.manifest]\nservice_description = \"from metadata\""
+    )
+    .unwrap();
+
+    let template_path = plugin_path.join("manifest.yaml.template");
+    let template = fs::read_to_string(&template_path)
+        .unwrap()
+        .replace("example service", "{{ metadata.service_description }}");
+    fs::write(&template_path, template).unwrap();
+
+    build_plugin(&helpers::BuildType::Debug, "0.1.0", plugin_path);
+
+    let manifest = fs::read_to_string(
+        plugin_path
+            .join("target")
+            .join("debug")
+            .join("test-plugin-context")
+            .join("0.1.0")
+            .join("manifest.yaml"),
+    )
+    .unwrap();
+    assert!(manifest.contains("name: test-plugin-context\n"));
+    assert!(manifest.contains("description: A plugin for picodata\n"));
+    assert!(manifest.contains("description: from metadata\n"));
+}
+
+#[test]
+fn test_cargo_build_invalid_manifest() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-manifest");
+
+    init_plugin("test-plugin-manifest");
+
+    let template_path = plugin_path.join("manifest.yaml.template");
+    let template = fs::read_to_string(&template_path).unwrap().replace(
+        "migration:",
+        "  - name: example_service\n    default_configuration: 1\nmigration:",
+    );
+    fs::write(&template_path, template).unwrap();
+
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("manifest.yaml:9:5: duplicate service name `example_service`"));
+    assert!(stderr.contains(
+        "manifest.yaml:9:5: `default_configuration` of service `example_service` must be a mapping"
+    ));
+}
+
+#[test]
+fn test_cargo_build_default_config() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-default-config");
+
+    init_plugin("test-plugin-default-config");
+
+    fs::copy(
+        "./tests/assets/default_config_build.rs",
+        plugin_path.join("build.rs"),
+    )
+    .unwrap();
+    let template_path = plugin_path.join("manifest.yaml.template");
+    let template = fs::read_to_string(&template_path).unwrap();
+    fs::write(
+        &template_path,
+        template.replace("    default_configuration:\n      value: example\n", ""),
+    )
+    .unwrap();
+
+    build_plugin(&helpers::BuildType::Debug, "0.1.0", plugin_path);
+
+    let manifest = fs::read_to_string(
+        plugin_path
+            .join("target")
+            .join("debug")
+            .join("test-plugin-default-config")
+            .join("0.1.0")
+            .join("manifest.yaml"),
+    )
+    .unwrap();
+    assert!(manifest.contains("value: from build.rs"));
+
+    // Default configuration written by hand must be the same
+    fs::write(&template_path, template).unwrap();
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "`default_configuration` of service `example_service` in the manifest template differs"
+    ));
+}
+
+#[test]
+fn test_cargo_build_template_error() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-template-error");
+
+    init_plugin("test-plugin-template-error");
+
+    fs::write(
+        plugin_path.join("manifest.yaml.template"),
+        "name: {{ name\nversion: {{ version }}\n",
+    )
+    .unwrap();
+
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid manifest template"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_cargo_build_migration_context_lint() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-migration-context");
+
+    init_plugin("test-plugin-migration-context");
+
+    // Example variable of the template is fine while migrations do not use the context
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("is not used"));
+
+    fs::write(
+        plugin_path.join("migrations/0002_context.sql"),
+        "-- pico.UP\nCREATE TABLE @_plugin_config.table_name (id INT PRIMARY KEY);\n-- pico.DOWN\nDROP TABLE @_plugin_config.table_name;\n",
+    )
+    .unwrap();
+    fs::write(
+        plugin_path.join("topology.toml"),
+        r#"
+[tier.default]
+replicasets = 1
+replication_factor = 1
+
+[plugin.test-plugin-migration-context]
+migration_context = [
+    { name = "example_name", value = "example_value" },
+]
+"#,
+    )
+    .unwrap();
+
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("migration context variable `table_name` has no value"));
+    assert!(stderr.contains("migration context variable `example_name` is not used"));
+}
+
+#[test]
+fn test_cargo_build_target() {
+    let plugin_path = Path::new("./tests/tmp/test-plugin-build-target");
+
+    init_plugin("test-plugin-build-target");
+
+    let rustc_info = Command::new("rustc").arg("-vV").output().unwrap();
+    let host = String::from_utf8(rustc_info.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("host: ").map(str::to_string))
+        .unwrap();
+
+    let output = Command::new("cargo")
+        .args(["build", "--target", &host])
+        .current_dir(plugin_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let plugin_dir = plugin_path
+        .join("target")
+        .join(&host)
+        .join("debug")
+        .join("test-plugin-build-target")
+        .join("0.1.0");
+    assert_path_existance(&plugin_dir.join("manifest.yaml"), false);
+    assert!(validate_symlink(
+        &plugin_dir.join(format!("libtest_plugin_build_target.{LIB_EXT}"))
+    ));
+}
+
 fn assert_plugin_build_artefacts(plugin_path: &Path, must_be_symlinks: bool) {
     let lib_path = plugin_path.join(format!("libtest_plugin_build.{LIB_EXT}"));
 
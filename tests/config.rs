@@ -1,8 +1,8 @@
 mod helpers;
 
 use helpers::{
-    exec_pike, get_picodata_table, init_plugin_workspace, run_cluster, CmdArguments, PLUGIN_DIR,
-    TESTS_DIR,
+    build_plugin, exec_pike, get_picodata_table, init_plugin, init_plugin_workspace, run_cluster,
+    BuildType, CmdArguments, PLUGIN_DIR, TESTS_DIR,
 };
 use rstest::rstest;
 use std::{
@@ -22,6 +22,7 @@ use pike::cluster::RunParamsBuilder;
 use pike::cluster::Tier;
 use pike::cluster::Topology;
 use pike::config::ApplyParamsBuilder;
+use pike::config::PluginConfigMap;
 
 const TOTAL_INSTANCES: i32 = 4;
 
@@ -131,6 +132,164 @@ fn test_corrupted_config() {
     );
 }
 
+#[test]
+fn test_config_apply_dry_run_and_prune() {
+    let _cluster_handle = run_cluster(
+        Duration::from_secs(120),
+        TOTAL_INSTANCES,
+        CmdArguments::default(),
+    )
+    .unwrap();
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config_apply = |args: &[&str]| {
+        let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+            .args(["pike", "config", "apply"])
+            .args(args)
+            .current_dir(PLUGIN_DIR)
+            .output()
+            .expect("Failed to run config apply");
+        assert!(output.status.success(), "config apply {args:?} failed");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let plugin_config = || {
+        get_picodata_table(
+            Path::new(PLUGIN_DIR),
+            Path::new("tmp"),
+            "_pico_plugin_config",
+        )
+    };
+
+    // Dry run only prints the difference
+    let stdout = config_apply(&["--dry-run"]);
+    assert!(stdout.contains(r#"value: "example" -> "changed""#));
+    assert!(!plugin_config().contains("changed"));
+
+    config_apply(&[]);
+    assert!(plugin_config().contains("changed"));
+
+    // Key removed from the file is kept without --prune
+    fs::write(
+        Path::new(PLUGIN_DIR).join("plugin_config.yaml"),
+        "example_service: {}\n",
+    )
+    .unwrap();
+    let stdout = config_apply(&[]);
+    assert!(stdout.contains(r#"= value: "changed" (kept on cluster)"#));
+    assert!(plugin_config().contains("changed"));
+
+    // Dry run previews the reset
+    let stdout = config_apply(&["--dry-run", "--prune"]);
+    assert!(stdout.contains(r#"- value: "changed" -> "example" (reset to default)"#));
+    assert!(plugin_config().contains("changed"));
+
+    // and the key is reset to the default value from the manifest with --prune
+    config_apply(&["--prune"]);
+    assert!(!plugin_config().contains("changed"));
+
+    // Service removed from the file is diffed and reset as a whole
+    fs::write(
+        Path::new(PLUGIN_DIR).join("plugin_config.yaml"),
+        "example_service:\n  value: changed\n",
+    )
+    .unwrap();
+    config_apply(&[]);
+    fs::write(Path::new(PLUGIN_DIR).join("plugin_config.yaml"), "{}\n").unwrap();
+    let stdout = config_apply(&["--dry-run"]);
+    assert!(stdout.contains("Service example_service"));
+    assert!(stdout.contains(r#"= value: "changed" (kept on cluster)"#));
+    config_apply(&["--prune"]);
+    assert!(!plugin_config().contains("changed"));
+}
+
+#[test]
+fn test_config_get() {
+    let _cluster_handle = run_cluster(
+        Duration::from_secs(120),
+        TOTAL_INSTANCES,
+        CmdArguments::default(),
+    )
+    .unwrap();
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config_get = |args: &[&str]| -> PluginConfigMap {
+        let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+            .args(["pike", "config", "get"])
+            .args(args)
+            .current_dir(PLUGIN_DIR)
+            .output()
+            .expect("Failed to run config get");
+        assert!(output.status.success(), "config get {args:?} failed");
+        serde_yaml::from_slice(&output.stdout).unwrap()
+    };
+
+    let config = config_get(&[]);
+    assert_eq!(
+        config["example_service"]["value"],
+        serde_yaml::Value::from("example")
+    );
+
+    // Printed config can be applied back
+    let params = ApplyParamsBuilder::default()
+        .plugin_path(PathBuf::from(PLUGIN_DIR))
+        .config_map(config)
+        .build()
+        .unwrap();
+    pike::config::apply(&params).unwrap();
+
+    let config = config_get(&["--service", "example_service"]);
+    assert_eq!(config.len(), 1);
+    assert!(config.contains_key("example_service"));
+}
+
+#[test]
+fn test_config_validation_and_schema() {
+    let plugin_path = Path::new(TESTS_DIR).join("test-plugin-config-schema");
+
+    init_plugin("test-plugin-config-schema");
+    build_plugin(&BuildType::Debug, "0.1.0", &plugin_path);
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let pike = |args: &[&str]| {
+        Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+            .arg("pike")
+            .args(args)
+            .current_dir(&plugin_path)
+            .output()
+            .expect("Failed to run pike")
+    };
+
+    // Unknown keys may be optional keys of the service, they are only reported
+    fs::write(
+        plugin_path.join("plugin_config.yaml"),
+        "example_service:\n  value: changed\n  optional: 1\n",
+    )
+    .unwrap();
+    let output = pike(&["config", "apply", "--dry-run"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("example_service.optional: unknown key"));
+    assert!(!stderr.contains("does not match default configuration"));
+
+    // Invalid config is rejected before connecting to the cluster
+    fs::write(
+        plugin_path.join("plugin_config.yaml"),
+        "example_service:\n  value: 1\n  valeu: changed\n",
+    )
+    .unwrap();
+    let output = pike(&["config", "apply"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("example_service.value: expected string, got integer"));
+
+    let output = pike(&["config", "schema"]);
+    assert!(output.status.success());
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        schema["properties"]["example_service"]["properties"]["value"]["default"],
+        "example"
+    );
+}
+
 #[test]
 #[allow(clippy::too_many_lines)]
 fn test_workspace_config_apply() {
@@ -14,7 +14,7 @@ use std::{
     fs::{self},
     io::ErrorKind,
     path::Path,
-    process::{Child, Command, Stdio},
+    process::{Child, Command, Output, Stdio},
     time::{Duration, Instant},
 };
 use tar::Archive;
@@ -43,6 +43,9 @@ pub struct CmdArguments {
     pub build_args: Vec<String>,
     pub plugin_args: Vec<String>,
     pub stop_args: Vec<String>,
+    pub run_env: Vec<(String, String)>,
+    // Write foreground output of `pike run` to the file instead of inheriting it
+    pub run_stdout: Option<PathBuf>,
 }
 
 pub struct Cluster {
@@ -259,13 +262,17 @@ pub fn run_cluster(
 
     // Setup the cluster
     let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-    let run_handler = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+    let mut run_cmd = Command::new(format!("{root_dir}/target/debug/cargo-pike"));
+    run_cmd
         .arg("pike")
         .arg("run")
         .args(&cluster_handle.cmd_args.run_args)
-        .current_dir(PLUGIN_DIR)
-        .spawn()
-        .unwrap();
+        .envs(cluster_handle.cmd_args.run_env.iter().cloned())
+        .current_dir(PLUGIN_DIR);
+    if let Some(stdout_path) = &cluster_handle.cmd_args.run_stdout {
+        run_cmd.stdout(File::create(stdout_path)?);
+    }
+    let run_handler = run_cmd.spawn().unwrap();
     cluster_handle.set_run_handler(run_handler);
 
     let start_time = Instant::now();
@@ -336,6 +343,24 @@ pub fn run_cluster(
     }
 }
 
+// Start the cluster with `run -d` and return output of the command,
+// the cluster is stopped once returned handle is dropped
+pub fn run_cluster_daemon(run_args: &[&str]) -> (Output, Cluster) {
+    let cluster_handle = Cluster::new(CmdArguments::default());
+    init_plugin(PLUGIN_NAME);
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+        .args(["pike", "--format", "json", "run", "-d"])
+        .args(run_args)
+        .current_dir(PLUGIN_DIR)
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("failed to execute pike");
+
+    (output, cluster_handle)
+}
+
 pub fn get_picodata_table(plugin_path: &Path, data_dir_path: &Path, table_name: &str) -> String {
     let mut picodata_admin =
         await_picodata_admin(Duration::from_secs(60), plugin_path, data_dir_path).unwrap();
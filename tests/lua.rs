@@ -0,0 +1,50 @@
+mod helpers;
+use helpers::{run_cluster, CmdArguments, PLUGIN_NAME, TESTS_DIR};
+use std::{
+    collections::BTreeMap,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+const TOTAL_INSTANCES: i32 = 4;
+
+#[test]
+fn test_lua_eval_on_all_instances() {
+    let _cluster_handle = run_cluster(
+        Duration::from_secs(120),
+        TOTAL_INSTANCES,
+        CmdArguments::default(),
+    )
+    .unwrap();
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+        .arg("pike")
+        .args([
+            "lua",
+            "pico.instance_info().tier",
+            "--all",
+            "--output",
+            "json",
+            "--plugin-path",
+            PLUGIN_NAME,
+        ])
+        .current_dir(TESTS_DIR)
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("failed to execute pike");
+
+    assert!(output.status.success(), "lua command failed");
+
+    let results: BTreeMap<String, serde_json::Value> =
+        serde_json::from_slice(&output.stdout).expect("lua output is not a valid JSON");
+
+    assert_eq!(results.len(), TOTAL_INSTANCES as usize);
+    for (instance_name, tier) in results {
+        assert_eq!(
+            tier, "default",
+            "unexpected tier of instance {instance_name}"
+        );
+    }
+}
@@ -1,8 +1,9 @@
 mod helpers;
 
-use helpers::{cleanup_dir, exec_pike, PLUGIN_DIR};
+use helpers::{cleanup_dir, exec_pike, PLUGIN_DIR, TESTS_DIR};
 use std::fs;
 use std::path::Path;
+use std::process::Command;
 
 #[test]
 fn test_cargo_plugin_new() {
@@ -56,3 +57,35 @@ fn test_cargo_plugin_new() {
     let contents = fs::read_to_string(root_dir.join("Cargo.toml")).unwrap();
     assert!(contents.contains("[workspace]"));
 }
+
+#[test]
+fn test_json_message_format() {
+    let plugin_name = "test-json-plugin";
+    let plugin_dir = Path::new(TESTS_DIR).join(plugin_name);
+    cleanup_dir(&plugin_dir);
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let pike_new = || {
+        let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+            .args(["pike", "--format", "json", "plugin", "new", plugin_name])
+            .arg("--without-git")
+            .current_dir(TESTS_DIR)
+            .output()
+            .expect("failed to execute pike");
+        let event: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        (output.status, event)
+    };
+
+    let (status, event) = pike_new();
+    assert!(status.success());
+    assert_eq!(event["event"], "plugin_created");
+    assert_eq!(event["name"], plugin_name);
+
+    // Second attempt must fail with a stable error code
+    let (status, event) = pike_new();
+    assert_eq!(status.code(), Some(1));
+    assert_eq!(event["event"], "error");
+    assert_eq!(event["code"], "path_already_exists");
+
+    cleanup_dir(&plugin_dir);
+}
@@ -1,10 +1,11 @@
 mod helpers;
 
-use helpers::{exec_pike, init_plugin, LIB_EXT, TESTS_DIR};
+use helpers::{cleanup_dir, exec_pike, init_plugin, init_plugin_workspace, LIB_EXT, TESTS_DIR};
 use std::{
     fs::{self, OpenOptions},
     io::Write,
     path::Path,
+    process::Command,
 };
 
 pub const PACK_PLUGIN_NAME: &str = "test-pack-plugin";
@@ -39,6 +40,388 @@ fn test_cargo_pack() {
         .exists());
     assert!(base_file_path.join("manifest.yaml").exists());
     assert!(base_file_path.join("migrations").is_dir());
+
+    let build_info: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(base_file_path.join("BUILD_INFO.json")).unwrap())
+            .unwrap();
+    assert_eq!(build_info["profile"], "release");
+    assert_eq!(build_info["pike_version"], env!("CARGO_PKG_VERSION"));
+    assert!(build_info["picodata_plugin_version"].is_string());
+    assert!(build_info["rustc_version"]
+        .as_str()
+        .unwrap()
+        .starts_with("rustc"));
+}
+
+#[test]
+fn test_cargo_pack_reproducible() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    let release_dir = Path::new(TESTS_DIR)
+        .join(PACK_PLUGIN_NAME)
+        .join("target")
+        .join("release");
+    let archive_path = release_dir.join("test-pack-plugin-0.1.0.tar.gz");
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+    let first_archive = fs::read(&archive_path).unwrap();
+
+    // Touch packed files, timestamps must not leak into the archive
+    let manifest_path = release_dir
+        .join("test-pack-plugin")
+        .join("0.1.0")
+        .join("manifest.yaml");
+    let manifest = fs::read(&manifest_path).unwrap();
+    fs::write(&manifest_path, manifest).unwrap();
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+    let second_archive = fs::read(&archive_path).unwrap();
+    assert!(
+        first_archive == second_archive,
+        "archives are not reproducible"
+    );
+
+    let checksum =
+        fs::read_to_string(release_dir.join("test-pack-plugin-0.1.0.tar.gz.sha256")).unwrap();
+    assert!(checksum.ends_with("  test-pack-plugin-0.1.0.tar.gz\n"));
+    assert_eq!(checksum.split_whitespace().next().unwrap().len(), 64);
+}
+
+#[test]
+fn test_cargo_pack_reproducible_after_clean() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    let plugin_path = Path::new(TESTS_DIR).join(PACK_PLUGIN_NAME);
+    let archive_path = plugin_path
+        .join("target")
+        .join("release")
+        .join("test-pack-plugin-0.1.0.tar.gz");
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+    let first_archive = fs::read(&archive_path).unwrap();
+
+    // Build script must rerun and produce the same BUILD_INFO.json
+    let status = Command::new("cargo")
+        .args(["clean", "--release", "-p", PACK_PLUGIN_NAME])
+        .current_dir(&plugin_path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "cargo clean failed");
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+    let second_archive = fs::read(&archive_path).unwrap();
+    assert!(
+        first_archive == second_archive,
+        "archives are not reproducible after clean rebuild"
+    );
+}
+
+#[test]
+fn test_cargo_pack_output_formats() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    let plugin_path = Path::new(TESTS_DIR).join(PACK_PLUGIN_NAME);
+    let lib_path = format!("test-pack-plugin/0.1.0/libtest_pack_plugin.{LIB_EXT}");
+
+    // Relative output path is resolved against the working directory of pike
+    let dist_dir = Path::new(TESTS_DIR).join("dist");
+    cleanup_dir(&dist_dir);
+
+    // Explicit archive path
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        PACK_PLUGIN_NAME,
+        "--archive-format",
+        "tar.zst",
+        "--compression-level",
+        "19",
+        "--output",
+        "dist/custom.tar.zst",
+    ]);
+
+    let archive_path = dist_dir.join("custom.tar.zst");
+    assert!(dist_dir.join("custom.tar.zst.sha256").exists());
+    let decoder = zstd::Decoder::new(fs::File::open(&archive_path).unwrap()).unwrap();
+    let unzipped_dir = plugin_path.join("unzipped_zst");
+    tar::Archive::new(decoder).unpack(&unzipped_dir).unwrap();
+    assert!(unzipped_dir.join(&lib_path).exists());
+
+    // Output directory keeps the default archive name
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        PACK_PLUGIN_NAME,
+        "--archive-format",
+        "zip",
+        "--output",
+        "dist/",
+    ]);
+
+    let archive_path = dist_dir.join("test-pack-plugin-0.1.0.zip");
+    let mut archive = zip::ZipArchive::new(fs::File::open(&archive_path).unwrap()).unwrap();
+    assert!(archive.by_name(&lib_path).is_ok());
+    assert!(archive
+        .by_name("test-pack-plugin/0.1.0/manifest.yaml")
+        .is_ok());
+}
+
+#[test]
+fn test_cargo_pack_split_debuginfo() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        PACK_PLUGIN_NAME,
+        "--split-debuginfo",
+    ]);
+
+    let release_path = Path::new(TESTS_DIR)
+        .join(PACK_PLUGIN_NAME)
+        .join("target")
+        .join("release");
+    let debug_archive_path = release_path.join("test-pack-plugin-0.1.0-debug.tar.gz");
+    assert!(release_path
+        .join("test-pack-plugin-0.1.0-debug.tar.gz.sha256")
+        .exists());
+
+    let unzipped_dir = release_path.join("unzipped_debug");
+    helpers::unpack_archive(&debug_archive_path, &unzipped_dir);
+    let build_id_dirs: Vec<_> = fs::read_dir(unzipped_dir.join(".build-id"))
+        .unwrap()
+        .collect();
+    assert_eq!(build_id_dirs.len(), 1);
+
+    // Stripped library is still a valid plugin
+    let archive_path = Path::new(PACK_PLUGIN_NAME)
+        .join("target")
+        .join("release")
+        .join("test-pack-plugin-0.1.0.tar.gz");
+    exec_pike([Path::new("plugin"), Path::new("verify"), &archive_path]);
+}
+
+#[test]
+fn test_cargo_pack_workspace_bundle() {
+    init_plugin_workspace("test_pack_workspace");
+    exec_pike([
+        "plugin",
+        "add",
+        "sub_plugin",
+        "--plugin-path",
+        "test_pack_workspace",
+    ]);
+
+    let workspace_path = Path::new(TESTS_DIR).join("test_pack_workspace");
+    let release_path = workspace_path.join("target").join("release");
+
+    // Only the selected plugin is packed
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        "test_pack_workspace",
+        "--package",
+        "sub_plugin",
+    ]);
+    assert!(release_path.join("sub_plugin-0.1.0.tar.gz").exists());
+    assert!(!release_path
+        .join("test_pack_workspace-0.1.0.tar.gz")
+        .exists());
+
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        "test_pack_workspace",
+        "--bundle",
+    ]);
+
+    let bundle_path = release_path.join("test_pack_workspace-bundle.tar.gz");
+    let unzipped_dir = workspace_path.join("unzipped_bundle");
+    helpers::unpack_archive(&bundle_path, &unzipped_dir);
+
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(unzipped_dir.join("bundle.json")).unwrap())
+            .unwrap();
+    assert_eq!(index["plugins"].as_array().unwrap().len(), 2);
+    assert!(unzipped_dir
+        .join("test_pack_workspace/0.1.0/manifest.yaml")
+        .exists());
+    assert!(unzipped_dir.join("sub_plugin/0.1.0/manifest.yaml").exists());
+
+    let bundle_path = Path::new("test_pack_workspace")
+        .join("target")
+        .join("release")
+        .join("test_pack_workspace-bundle.tar.gz");
+    exec_pike([Path::new("plugin"), Path::new("verify"), &bundle_path]);
+}
+
+#[test]
+fn test_cargo_pack_workspace_version() {
+    init_plugin_workspace("test_pack_ws_version");
+
+    // Version inherited from the workspace and resolved by cargo
+    let workspace_path = Path::new(TESTS_DIR).join("test_pack_ws_version");
+    let workspace_manifest_path = workspace_path.join("Cargo.toml");
+    let mut workspace_manifest = fs::read_to_string(&workspace_manifest_path).unwrap();
+    workspace_manifest.push_str("\n[workspace.package]\nversion = \"0.3.0\"\n");
+    fs::write(&workspace_manifest_path, workspace_manifest).unwrap();
+
+    let member_manifest_path = workspace_path
+        .join("test_pack_ws_version")
+        .join("Cargo.toml");
+    let member_manifest = fs::read_to_string(&member_manifest_path)
+        .unwrap()
+        .replace("version = \"0.1.0\"", "version.workspace = true");
+    fs::write(&member_manifest_path, member_manifest).unwrap();
+
+    exec_pike(["plugin", "pack", "--plugin-path", "test_pack_ws_version"]);
+
+    assert!(workspace_path
+        .join("target")
+        .join("release")
+        .join("test_pack_ws_version-0.3.0.tar.gz")
+        .exists());
+}
+
+#[test]
+fn test_cargo_pack_metadata_config() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    let plugin_path = Path::new(TESTS_DIR).join(PACK_PLUGIN_NAME);
+    fs::create_dir_all(plugin_path.join("bin")).unwrap();
+    fs::write(plugin_path.join("bin").join("run.sh"), "echo run").unwrap();
+    fs::write(plugin_path.join("bin").join("scratch.sh"), "echo scratch").unwrap();
+    fs::create_dir_all(plugin_path.join("docs")).unwrap();
+    fs::write(plugin_path.join("docs").join("usage.md"), "usage").unwrap();
+    fs::write(plugin_path.join("docs").join("notes.txt"), "notes").unwrap();
+
+    let mut cargo_manifest = OpenOptions::new()
+        .append(true)
+        .open(plugin_path.join("Cargo.toml"))
+        .unwrap();
+    cargo_manifest
+        .write_all(
+            br#"
+[package.metadata.pike.pack]
+include = ["bin", "docs/*.md"]
+exclude = ["bin/scratch.sh"]
+rename = { "docs" = "doc" }
+mode = { "bin/*.sh" = "755" }
+"#,
+        )
+        .unwrap();
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+
+    let release_path = plugin_path.join("target").join("release");
+    let unzipped_dir = release_path.join("unzipped_metadata");
+    helpers::unpack_archive(
+        &release_path.join("test-pack-plugin-0.1.0.tar.gz"),
+        &unzipped_dir,
+    );
+    let base_dir = unzipped_dir.join(PACK_PLUGIN_NAME).join("0.1.0");
+
+    let run_script = base_dir.join("bin").join("run.sh");
+    assert!(run_script.exists());
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&run_script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+    assert!(!base_dir.join("bin").join("scratch.sh").exists());
+    assert!(base_dir.join("doc").join("usage.md").exists());
+    assert!(!base_dir.join("doc").join("notes.txt").exists());
+    assert!(!base_dir.join("docs").exists());
+    assert!(base_dir.join("manifest.yaml").exists());
+}
+
+#[test]
+fn test_cargo_pack_lock_migrations() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    exec_pike([
+        "plugin",
+        "pack",
+        "--plugin-path",
+        PACK_PLUGIN_NAME,
+        "--lock-migrations",
+    ]);
+
+    let plugin_path = Path::new(TESTS_DIR).join(PACK_PLUGIN_NAME);
+    let lock = fs::read_to_string(plugin_path.join("migrations.lock")).unwrap();
+    assert!(lock.contains("\"0001_init.sql\" = "));
+
+    // Released migration must not change
+    let mut migration = OpenOptions::new()
+        .append(true)
+        .open(plugin_path.join("migrations").join("0001_init.sql"))
+        .unwrap();
+    writeln!(migration, "-- changed").unwrap();
+
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(&plugin_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("0001_init.sql: released migration is changed"));
+}
+
+#[test]
+fn test_plugin_verify() {
+    init_plugin(PACK_PLUGIN_NAME);
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+
+    let archive_path = Path::new(PACK_PLUGIN_NAME)
+        .join("target")
+        .join("release")
+        .join("test-pack-plugin-0.1.0.tar.gz");
+    exec_pike([Path::new("plugin"), Path::new("verify"), &archive_path]);
+
+    // Archive with a stray file and without the library and migrations
+    let broken_archive_path = Path::new(TESTS_DIR)
+        .join(PACK_PLUGIN_NAME)
+        .join("broken.tar");
+    let mut tarball = tar::Builder::new(fs::File::create(&broken_archive_path).unwrap());
+    let manifest =
+        "name: test-pack-plugin\nversion: 0.1.0\nmigration:\n- migrations/0001_init.sql\n";
+    for (path, data) in [
+        ("test-pack-plugin/0.1.0/manifest.yaml", manifest),
+        ("stray.txt", "stray"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        tarball
+            .append_data(&mut header, path, data.as_bytes())
+            .unwrap();
+    }
+    tarball.finish().unwrap();
+    drop(tarball);
+
+    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let output = Command::new(format!("{root_dir}/target/debug/cargo-pike"))
+        .args(["pike", "--format", "json", "plugin", "verify"])
+        .arg(&broken_archive_path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    let event: serde_json::Value = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(event["event"], "archive_verified");
+    let problems = event["problems"].as_array().unwrap();
+    assert_eq!(problems.len(), 3, "unexpected problems: {problems:?}");
 }
 
 #[test]
@@ -221,3 +604,48 @@ fn test_custom_assets_with_targets() {
         .join("lib.rs")
         .exists());
 }
+
+#[test]
+fn test_glob_and_generated_assets() {
+    let tests_dir = Path::new(TESTS_DIR);
+    let plugin_path = tests_dir.join(PACK_PLUGIN_NAME);
+
+    init_plugin(PACK_PLUGIN_NAME);
+
+    fs::copy(
+        tests_dir.join("../assets/glob_assets_build.rs"),
+        plugin_path.join("build.rs"),
+    )
+    .unwrap();
+
+    exec_pike(["plugin", "pack", "--plugin-path", PACK_PLUGIN_NAME]);
+
+    let unzipped_dir = plugin_path.join("unzipped_glob");
+    helpers::unpack_archive(
+        &plugin_path
+            .join("target")
+            .join("release")
+            .join("test-pack-plugin-0.1.0.tar.gz"),
+        &unzipped_dir,
+    );
+
+    let assets_file_path = unzipped_dir.join(PACK_PLUGIN_NAME).join("0.1.0");
+    assert!(assets_file_path.join("0001_init.sql").exists());
+    assert!(assets_file_path.join("sources").join("lib.rs").exists());
+    assert!(assets_file_path.join("plugin_config.yaml").exists());
+    assert_eq!(
+        fs::read_to_string(assets_file_path.join("generated").join("version.txt")).unwrap(),
+        "0.1.0"
+    );
+
+    // Missing required asset fails the build
+    fs::remove_file(plugin_path.join("plugin_config.yaml")).unwrap();
+    let output = Command::new("cargo")
+        .arg("build")
+        .current_dir(&plugin_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Couldn't find custom asset plugin_config.yaml"));
+}
@@ -2,12 +2,14 @@ mod helpers;
 
 use helpers::{
     cleanup_dir, exec_pike, get_picodata_table, init_plugin, init_plugin_workspace, run_cluster,
-    CmdArguments, LIB_EXT, PLUGIN_DIR, PLUGIN_NAME, TESTS_DIR,
+    run_cluster_daemon, CmdArguments, LIB_EXT, PLUGIN_DIR, PLUGIN_NAME, TESTS_DIR,
 };
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
 use pike::cluster::{run, MigrationContextVar, Plugin, RunParamsBuilder, Service, Tier, Topology};
 use std::collections::BTreeMap;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{BufRead, Write};
 use std::process::Command;
 use std::{
     fs::{self},
@@ -75,6 +77,11 @@ fn test_cluster_daemon_and_arguments() {
     let _cluster_handle =
         run_cluster(Duration::from_secs(120), TOTAL_INSTANCES, run_params).unwrap();
 
+    // Detached cluster is owned by the supervisor
+    let supervisor_pid_path = Path::new(PLUGIN_DIR).join("tmp").join("supervisor.pid");
+    let supervisor_pid = fs::read_to_string(supervisor_pid_path).unwrap();
+    assert!(supervisor_pid.trim().parse::<u32>().is_ok());
+
     // Validate each instances's PID
     for entry in fs::read_dir(Path::new(PLUGIN_DIR).join("tmp").join("cluster")).unwrap() {
         let entry = entry.unwrap();
@@ -88,6 +95,156 @@ fn test_cluster_daemon_and_arguments() {
     }
 }
 
+#[test]
+fn test_cluster_daemon_reports_readiness() {
+    let (output, _cluster_handle) = run_cluster_daemon(&[]);
+    assert!(output.status.success(), "run -d failed");
+
+    let events: Vec<serde_json::Value> = output
+        .stdout
+        .lines()
+        .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+        .collect();
+    let started = events
+        .iter()
+        .filter(|e| e["event"] == "instance_started")
+        .count();
+    assert_eq!(started, TOTAL_INSTANCES as usize);
+    assert!(events.iter().any(|e| e["event"] == "daemon_started"));
+
+    // `run -d` returns only once every instance is Online
+    let instances = get_picodata_table(Path::new(PLUGIN_DIR), Path::new("tmp"), "_pico_instance");
+    // Current and target states of each instance
+    assert_eq!(instances.matches("Online").count(), 8);
+}
+
+#[test]
+fn test_cluster_daemon_teardown_on_failure() {
+    // Instances can not become Online instantly, so bootstrap fails
+    let (output, _cluster_handle) = run_cluster_daemon(&["--ready-timeout", "0s"]);
+    assert!(!output.status.success(), "run -d must fail");
+
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(error["event"], "error");
+    assert_eq!(error["code"], "cluster_not_ready");
+
+    let data_dir = Path::new(PLUGIN_DIR).join("tmp");
+    assert!(!data_dir.join("supervisor.pid").exists());
+
+    // Supervisor kills already started instances
+    let start = Instant::now();
+    for entry in fs::read_dir(data_dir.join("cluster")).unwrap() {
+        let pid_path = entry.unwrap().path().join("pid");
+        let Ok(pid) = fs::read_to_string(&pid_path) else {
+            continue;
+        };
+        let pid = Pid::from_raw(pid.trim().parse().unwrap());
+        while kill(pid, None).is_ok() {
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "instance {pid} is still running"
+            );
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+#[test]
+fn test_cluster_log_filter() {
+    let stdout_path = Path::new(TESTS_DIR).join("log_filter_stdout.log");
+    let run_params = CmdArguments {
+        run_args: [
+            "--log-level",
+            "info",
+            "--log-grep",
+            " I> ",
+            "--quiet-instances",
+            "i2,i3",
+            // Keep instance name prefixes plain to match them below
+            "--disable-colors",
+        ]
+        .iter()
+        .map(|&s| s.into())
+        .collect(),
+        // Make instances write lines below the filtered level
+        run_env: vec![("PICODATA_LOG_LEVEL".into(), "verbose".into())],
+        run_stdout: Some(stdout_path.clone()),
+        ..Default::default()
+    };
+
+    let _cluster_handle =
+        run_cluster(Duration::from_secs(120), TOTAL_INSTANCES, run_params).unwrap();
+
+    // Log files must still receive full output of instances
+    for instance in ["i1", "i2", "i3"] {
+        let log_path = Path::new(PLUGIN_DIR)
+            .join("tmp")
+            .join("cluster")
+            .join(instance)
+            .join("picodata.log");
+        let log_content = fs::read_to_string(log_path).unwrap();
+        assert!(log_content.contains(" I> "));
+        assert!(log_content.contains(" V> "));
+    }
+
+    let foreground = fs::read_to_string(stdout_path).unwrap();
+    let foreground_lines: Vec<&str> = foreground.lines().collect();
+    assert!(
+        !foreground_lines.is_empty(),
+        "foreground output of instances is empty"
+    );
+
+    // Instances are renamed after start, output is prefixed either with
+    // the data dir name or with the name given by picodata
+    let cluster_dir = Path::new(PLUGIN_DIR).join("tmp").join("cluster");
+    let mut quiet_prefixes = vec!["i2: ".to_string(), "i3: ".to_string()];
+    for entry in fs::read_dir(&cluster_dir).unwrap() {
+        let entry = entry.unwrap();
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        if target.ends_with("i2") || target.ends_with("i3") {
+            quiet_prefixes.push(format!("{}: ", entry.file_name().to_string_lossy()));
+        }
+    }
+
+    for line in &foreground_lines {
+        // Lines below the level are filtered out
+        assert!(!line.contains(" V> "), "line below log level: {line}");
+        // Lines not matching the grep are dropped
+        assert!(line.contains(" I> "), "line does not match grep: {line}");
+        // Quiet instances are not shown at all
+        assert!(
+            !quiet_prefixes.iter().any(|p| line.starts_with(p.as_str())),
+            "line of quiet instance: {line}"
+        );
+    }
+}
+
+#[test]
+fn test_cluster_log_rotation() {
+    let run_params = CmdArguments {
+        run_args: ["--log-max-size", "4K", "--log-retention", "2"]
+            .iter()
+            .map(|&s| s.into())
+            .collect(),
+        ..Default::default()
+    };
+
+    let _cluster_handle =
+        run_cluster(Duration::from_secs(120), TOTAL_INSTANCES, run_params).unwrap();
+
+    let instance_dir = Path::new(PLUGIN_DIR).join("tmp").join("cluster").join("i1");
+    assert!(
+        fs::metadata(instance_dir.join("picodata.log"))
+            .unwrap()
+            .len()
+            <= 4096
+    );
+    assert!(instance_dir.join("picodata.log.1").exists());
+    assert!(!instance_dir.join("picodata.log.3").exists());
+}
+
 // This code tests Pike's public interface.
 // Any changes are potential BREAKING changes.
 #[test]
@@ -246,8 +403,10 @@ fn test_cluster_failure() {
         .open(plugin_path.join("migrations/0001_init.sql"))
         .unwrap();
 
+    writeln!(file, "-- pico.UP").unwrap();
     writeln!(file, "Scooby do be do, where are you?").unwrap();
     writeln!(file, "We got some work to do nooow!").unwrap();
+    writeln!(file, "-- pico.DOWN").unwrap();
 
     let tiers = BTreeMap::from([(
         "default".to_string(),